@@ -0,0 +1,925 @@
+use crate::cli::{build_globset, build_regexset};
+use crate::config::{DeleteConfig, EntryKind, ScanResult};
+use crate::dedupe::{DuplicateGroup, KeepPolicy, find_duplicates};
+use crate::delete::{run_dedupe_pipeline, run_deletion_pipeline, run_plan_pipeline};
+use crate::error::DeleterError;
+use crate::filter::{Filter, RegexTarget, TimeField};
+use crate::gitignore::IgnoreMode;
+use crate::limits::{Budget, Limit, RunClock, RunLimits};
+use crate::magic::MimePattern;
+use crate::namedate::{NameDate, NameDateFallback};
+use crate::owner::{PermMatch, group_id, user_id};
+use crate::plan::{DriftPolicy, Plan, PlanEntry};
+use crate::preset::{DirRules, find_preset};
+use crate::protect::ProtectedPaths;
+use crate::report::DeletionReport;
+use crate::retention::{Gfs, GroupPattern, KeptEntry, RetainBy, Retention};
+use crate::scan::{ScanSummary, spawn_scanners};
+use crate::space::{FreeGoal, FreeOrder, FreeTarget, one_filesystem};
+use crate::storage::StorageKind;
+use indicatif::ProgressBar;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::{fs, sync::mpsc};
+use tracing::{info, warn};
+
+/// Builder for a [`Spacefree`] deletion run
+#[derive(Clone, Default)]
+pub struct SpacefreeBuilder {
+    paths: Vec<PathBuf>,
+    globs: Vec<String>,
+    excludes: Vec<String>,
+    regexes: Vec<String>,
+    exclude_regexes: Vec<String>,
+    regex_target: RegexTarget,
+    mime: Vec<MimePattern>,
+    presets: Vec<String>,
+    min_size: u64,
+    max_size: Option<u64>,
+    min_age: Option<u64>,
+    max_age: Option<u64>,
+    older_than: Option<SystemTime>,
+    newer_than: Option<SystemTime>,
+    time_field: TimeField,
+    name_date: Option<NameDate>,
+    name_date_fallback: NameDateFallback,
+    use_trash: bool,
+    dry_run: bool,
+    parallelism: usize,
+    verbose: bool,
+    dirs: bool,
+    types: Vec<EntryKind>,
+    broken_symlinks: bool,
+    follow_symlinks: bool,
+    min_depth: usize,
+    max_depth: Option<usize>,
+    one_file_system: bool,
+    keep_newest: Option<usize>,
+    keep_largest: Option<usize>,
+    gfs: Gfs,
+    keep_groups: Vec<String>,
+    free_goal: Option<FreeGoal>,
+    free_order: FreeOrder,
+    limits: RunLimits,
+    ignore_mode: IgnoreMode,
+    user: Option<String>,
+    group: Option<String>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    perm: Option<PermMatch>,
+    protect: Vec<PathBuf>,
+    allow_protected: bool,
+    custom_filters: Vec<Arc<dyn Filter>>,
+    storage_kind: Option<StorageKind>,
+    log_path: Option<PathBuf>,
+    progress: Option<ProgressBar>,
+}
+
+impl SpacefreeBuilder {
+    /// Add a directory or file to process
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Add several directories or files to process
+    pub fn paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a glob pattern for files to delete [default: **/* (all files)]
+    pub fn glob(mut self, pattern: impl Into<String>) -> Self {
+        self.globs.push(pattern.into());
+        self
+    }
+
+    /// Add a glob pattern to exclude
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.excludes.push(pattern.into());
+        self
+    }
+
+    /// Add a regex the file name (or relative path) must match; any one of several suffices
+    pub fn regex(mut self, pattern: impl Into<String>) -> Self {
+        self.regexes.push(pattern.into());
+        self
+    }
+
+    /// Add a regex for file names (or relative paths) to exclude
+    pub fn exclude_regex(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude_regexes.push(pattern.into());
+        self
+    }
+
+    /// Match regexes against the file name or the path relative to the root [default: name]
+    pub fn regex_target(mut self, target: RegexTarget) -> Self {
+        self.regex_target = target;
+        self
+    }
+
+    /// Add a content type the file's magic bytes must match; any one of several suffices
+    pub fn mime(mut self, pattern: MimePattern) -> Self {
+        self.mime.push(pattern);
+        self
+    }
+
+    /// Add a built-in cleanup preset by name (see [`crate::preset::PRESETS`])
+    pub fn preset(mut self, name: impl Into<String>) -> Self {
+        self.presets.push(name.into());
+        self
+    }
+
+    /// Minimum file size in bytes
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Maximum file size in bytes
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Only delete files older than this many seconds
+    pub fn min_age(mut self, secs: u64) -> Self {
+        self.min_age = Some(secs);
+        self
+    }
+
+    /// Only delete files newer than this many seconds
+    pub fn max_age(mut self, secs: u64) -> Self {
+        self.max_age = Some(secs);
+        self
+    }
+
+    /// Only delete files last changed before this time
+    pub fn older_than(mut self, time: SystemTime) -> Self {
+        self.older_than = Some(time);
+        self
+    }
+
+    /// Only delete files last changed at or after this time
+    pub fn newer_than(mut self, time: SystemTime) -> Self {
+        self.newer_than = Some(time);
+        self
+    }
+
+    /// Timestamp the age and date filters measure from [default: mtime]
+    pub fn time_field(mut self, field: TimeField) -> Self {
+        self.time_field = field;
+        self
+    }
+
+    /// Read ages from a date embedded in file names instead of `time_field`
+    pub fn name_date(mut self, format: NameDate) -> Self {
+        self.name_date = Some(format);
+        self
+    }
+
+    /// What to do with names `name_date` finds no date in [default: skip]
+    pub fn name_date_fallback(mut self, fallback: NameDateFallback) -> Self {
+        self.name_date_fallback = fallback;
+        self
+    }
+
+    /// Move to system trash instead of permanent delete
+    pub fn trash(mut self, yes: bool) -> Self {
+        self.use_trash = yes;
+        self
+    }
+
+    /// Walk and report without deleting anything
+    pub fn dry_run(mut self, yes: bool) -> Self {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Number of parallel workers (0 = auto-detect based on storage type)
+    pub fn parallelism(mut self, workers: usize) -> Self {
+        self.parallelism = workers;
+        self
+    }
+
+    /// Print every processed path through the progress bar, or log it at info level when the bar is hidden
+    pub fn verbose(mut self, yes: bool) -> Self {
+        self.verbose = yes;
+        self
+    }
+
+    /// Delete empty directories as well as files
+    pub fn dirs(mut self, yes: bool) -> Self {
+        self.dirs = yes;
+        self
+    }
+
+    /// Select entries of this kind; several calls combine [default: regular files]
+    pub fn entry_type(mut self, kind: EntryKind) -> Self {
+        if !self.types.contains(&kind) {
+            self.types.push(kind);
+        }
+        self
+    }
+
+    /// Also select symbolic links whose target does not exist
+    pub fn broken_symlinks(mut self, yes: bool) -> Self {
+        self.broken_symlinks = yes;
+        self
+    }
+
+    /// Follow symbolic links during directory traversal
+    pub fn follow_symlinks(mut self, yes: bool) -> Self {
+        self.follow_symlinks = yes;
+        self
+    }
+
+    /// Skip entries shallower than this (the root is depth 0, its entries depth 1)
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Do not descend more than this many levels below each root
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Stay on the filesystem of each root (Unix)
+    pub fn one_file_system(mut self, yes: bool) -> Self {
+        self.one_file_system = yes;
+        self
+    }
+
+    /// Leave the `count` newest matches of each directory or group undeleted
+    pub fn keep_newest(mut self, count: usize) -> Self {
+        self.keep_newest = Some(count);
+        self
+    }
+
+    /// Leave the `count` largest matches of each directory or group undeleted
+    pub fn keep_largest(mut self, count: usize) -> Self {
+        self.keep_largest = Some(count);
+        self
+    }
+
+    /// Keep the newest match of each of the last `count` days that have one
+    pub fn keep_daily(mut self, count: usize) -> Self {
+        self.gfs.daily = count;
+        self
+    }
+
+    /// Keep the newest match of each of the last `count` ISO weeks that have one
+    pub fn keep_weekly(mut self, count: usize) -> Self {
+        self.gfs.weekly = count;
+        self
+    }
+
+    /// Keep the newest match of each of the last `count` months that have one
+    pub fn keep_monthly(mut self, count: usize) -> Self {
+        self.gfs.monthly = count;
+        self
+    }
+
+    /// Keep the newest match of each of the last `count` years that have one
+    pub fn keep_yearly(mut self, count: usize) -> Self {
+        self.gfs.yearly = count;
+        self
+    }
+
+    /// Count retention separately for file names matching this glob in each directory
+    pub fn keep_group(mut self, pattern: impl Into<String>) -> Self {
+        self.keep_groups.push(pattern.into());
+        self
+    }
+
+    /// Delete matches only until the first path's filesystem has this much free (Unix)
+    pub fn free_target(mut self, goal: FreeGoal) -> Self {
+        self.free_goal = Some(goal);
+        self
+    }
+
+    /// Which matches go first when working toward a free-space target [default: oldest]
+    pub fn free_order(mut self, order: FreeOrder) -> Self {
+        self.free_order = order;
+        self
+    }
+
+    /// Stop the run after deleting this many items
+    pub fn max_count(mut self, count: u64) -> Self {
+        self.limits.max_count = Some(count);
+        self
+    }
+
+    /// Stop the run before deleting more than this many bytes
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.limits.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Stop scanning and deleting once this much working time is used. Previews,
+    /// duplicate searches and deletions all count; the time between calls does not
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.limits.time_limit = Some(limit);
+        self
+    }
+
+    /// Honor `.gitignore`/`.ignore` rules, or select only ignored files
+    pub fn ignore_mode(mut self, mode: IgnoreMode) -> Self {
+        self.ignore_mode = mode;
+        self
+    }
+
+    /// Only select files owned by this user name (Unix)
+    pub fn user(mut self, name: impl Into<String>) -> Self {
+        self.user = Some(name.into());
+        self
+    }
+
+    /// Only select files owned by this group name (Unix)
+    pub fn group(mut self, name: impl Into<String>) -> Self {
+        self.group = Some(name.into());
+        self
+    }
+
+    /// Only select files owned by this numeric user id (Unix)
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Only select files owned by this numeric group id (Unix)
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Only select files whose permission bits match (Unix)
+    pub fn perm(mut self, perm: PermMatch) -> Self {
+        self.perm = Some(perm);
+        self
+    }
+
+    /// Protect a path and everything under it, in addition to the built-in list
+    pub fn protect(mut self, path: impl Into<PathBuf>) -> Self {
+        self.protect.push(path.into());
+        self
+    }
+
+    /// Disable the protected-path denylist (built-in and configured)
+    pub fn allow_protected(mut self, yes: bool) -> Self {
+        self.allow_protected = yes;
+        self
+    }
+
+    /// Add a custom predicate; candidates must pass it as well as the built-in filters
+    pub fn filter(mut self, filter: impl Filter + 'static) -> Self {
+        self.custom_filters.push(Arc::new(filter));
+        self
+    }
+
+    /// Override storage detection (skips probing the first path)
+    pub fn storage_kind(mut self, kind: StorageKind) -> Self {
+        self.storage_kind = Some(kind);
+        self
+    }
+
+    /// Write an NDJSON log of deleted items to this path
+    pub fn log_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.log_path = Some(path.into());
+        self
+    }
+
+    /// Progress bar to drive while deleting [default: hidden]
+    pub fn progress(mut self, pb: ProgressBar) -> Self {
+        self.progress = Some(pb);
+        self
+    }
+
+    /// Validate options, detect storage and compile the delete configuration
+    pub fn build(self) -> Result<Spacefree, DeleterError> {
+        if self.paths.is_empty() {
+            return Err(DeleterError::NoValidPaths);
+        }
+
+        // Refuse protected roots up front; entries inside protected trees are pruned while scanning
+        let protected = if self.allow_protected {
+            ProtectedPaths::none()
+        } else {
+            ProtectedPaths::new(&self.protect)
+        };
+        for path in &self.paths {
+            let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            if protected.contains(&canonical) {
+                return Err(DeleterError::Protected(path.display().to_string()));
+            }
+        }
+
+        // Owner names resolve to ids once; a name and an id for the same field must agree
+        let uid = resolve_id("user", self.user.as_deref(), self.uid, user_id)?;
+        let gid = resolve_id("group", self.group.as_deref(), self.gid, group_id)?;
+        if cfg!(not(unix)) && (uid.is_some() || gid.is_some() || self.perm.is_some()) {
+            return Err(DeleterError::Config(
+                "owner filters are only supported on Unix".to_string(),
+            ));
+        }
+
+        if cfg!(not(unix)) && self.time_field == TimeField::Ctime {
+            return Err(DeleterError::Config(
+                "--time-field ctime is only supported on Unix".to_string(),
+            ));
+        }
+        if cfg!(not(unix)) && self.one_file_system {
+            return Err(DeleterError::Config(
+                "--one-file-system is only supported on Unix".to_string(),
+            ));
+        }
+
+        // Without --type, regular files are selected unless only broken links are wanted
+        let mut types = self.types;
+        if types.is_empty() && !self.broken_symlinks {
+            types.push(EntryKind::File);
+        }
+        if self.dirs && !types.contains(&EntryKind::Dir) {
+            types.push(EntryKind::Dir);
+        }
+        if self.follow_symlinks && (types.contains(&EntryKind::Symlink) || self.broken_symlinks) {
+            return Err(DeleterError::Config(
+                "symbolic links cannot be selected with --follow-symlinks".to_string(),
+            ));
+        }
+
+        // Contradictory bounds would silently select nothing
+        if self.max_depth.is_some_and(|max| self.min_depth > max) {
+            return Err(DeleterError::Config(
+                "--min-depth is larger than --max-depth, so nothing can match".to_string(),
+            ));
+        }
+        if let (Some(min), Some(max)) = (self.min_age, self.max_age) {
+            if min > max {
+                return Err(DeleterError::Config(
+                    "--min-age is larger than --max-age, so nothing can match".to_string(),
+                ));
+            }
+        }
+        if let (Some(older), Some(newer)) = (self.older_than, self.newer_than) {
+            if newer >= older {
+                return Err(DeleterError::Config(
+                    "--newer-than is not before --older-than, so nothing can match".to_string(),
+                ));
+            }
+        }
+        let retention = match (self.keep_newest, self.keep_largest) {
+            (Some(_), Some(_)) => {
+                return Err(DeleterError::Config(
+                    "--keep-newest and --keep-largest cannot be combined".to_string(),
+                ));
+            }
+            (_, Some(_)) if !self.gfs.is_empty() => {
+                return Err(DeleterError::Config(
+                    "--keep-largest cannot be combined with --keep-daily/weekly/monthly/yearly"
+                        .to_string(),
+                ));
+            }
+            (Some(count), None) => Some((count, RetainBy::Newest)),
+            (None, Some(count)) => Some((count, RetainBy::Largest)),
+            (None, None) if !self.gfs.is_empty() => Some((0, RetainBy::Newest)),
+            (None, None) if !self.keep_groups.is_empty() => {
+                return Err(DeleterError::Config(
+                    "--keep-group needs a retention rule such as --keep-newest".to_string(),
+                ));
+            }
+            (None, None) => None,
+        };
+        let retention = match retention {
+            Some((count, by)) => Some(Retention {
+                count,
+                by,
+                gfs: self.gfs,
+                groups: self
+                    .keep_groups
+                    .iter()
+                    .map(|p| GroupPattern::new(p))
+                    .collect::<Result<_, _>>()?,
+            }),
+            None => None,
+        };
+
+        let free_target = match self.free_goal {
+            Some(_) if cfg!(not(unix)) => {
+                return Err(DeleterError::Config(
+                    "free-space targets are only supported on Unix".to_string(),
+                ));
+            }
+            Some(_) if self.use_trash => {
+                return Err(DeleterError::Config(
+                    "a free-space target needs permanent deletion; trashed files still use the disk"
+                        .to_string(),
+                ));
+            }
+            Some(_) if !one_filesystem(&self.paths) => {
+                return Err(DeleterError::Config(
+                    "a free-space target needs every path on the same filesystem".to_string(),
+                ));
+            }
+            Some(goal) => {
+                info!(
+                    "Deleting {} first until {} is {}",
+                    self.free_order,
+                    self.paths[0].display(),
+                    goal
+                );
+                Some(FreeTarget {
+                    goal,
+                    order: self.free_order,
+                    path: self.paths[0].clone(),
+                })
+            }
+            None => None,
+        };
+
+        if self.limits.max_count == Some(0) || self.limits.time_limit == Some(Duration::ZERO) {
+            return Err(DeleterError::Config(
+                "--max-count and --time-limit must be above zero".to_string(),
+            ));
+        }
+        if !self.limits.is_empty() {
+            info!("Run limits: {}", self.limits);
+        }
+
+        // Describe the selection as the user wrote it: globs plus preset names
+        let mut described = self.globs.clone();
+        described.extend(self.regexes.iter().map(|r| format!("regex {}", r)));
+        described.extend(self.mime.iter().map(|m| format!("mime {}", m)));
+        if !self.presets.is_empty() {
+            described.push(format!("preset {}", self.presets.join("+")));
+        }
+        if described.is_empty() {
+            described.push("**/*".to_string());
+        }
+        if let Some(retention) = &retention {
+            described.push(retention.to_string());
+        }
+        if let Some(target) = &free_target {
+            described.push(format!("until {}", target.goal));
+        }
+        let glob_pattern = described.join(", ");
+
+        // Presets add include/exclude globs and directory rules on top of the user's patterns
+        let mut includes = self.globs;
+        let mut excludes = self.excludes;
+        let mut rules = Vec::new();
+        for name in &self.presets {
+            let preset = find_preset(name)?;
+            includes.extend(preset.include.iter().map(|g| g.to_string()));
+            excludes.extend(preset.exclude.iter().map(|g| g.to_string()));
+            rules.extend(preset.dirs.iter().copied());
+        }
+        let match_all = rules.is_empty() && includes.iter().all(|g| g == "**/*");
+
+        let (globset, exclude_glob) = build_globset(&includes, &excludes)?;
+        let regex = build_regexset(&self.regexes)?;
+        let exclude_regex = build_regexset(&self.exclude_regexes)?;
+
+        // Detect storage type from the first path; it drives sorting and auto parallelism
+        let storage_kind = self.storage_kind.unwrap_or_else(|| {
+            self.paths
+                .first()
+                .map(|p| StorageKind::from_path(p))
+                .unwrap_or(StorageKind::Unknown)
+        });
+        let parallelism = if self.parallelism == 0 {
+            storage_kind.optimal_parallelism()
+        } else {
+            self.parallelism
+        };
+
+        let config = DeleteConfig {
+            use_trash: self.use_trash,
+            dry_run: self.dry_run,
+            parallelism,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            min_age: self.min_age,
+            max_age: self.max_age,
+            older_than: self.older_than,
+            newer_than: self.newer_than,
+            time_field: self.time_field,
+            name_date: self.name_date,
+            name_date_fallback: self.name_date_fallback,
+            verbose: self.verbose,
+            dirs: types.contains(&EntryKind::Dir),
+            types,
+            broken_symlinks: self.broken_symlinks,
+            follow_symlinks: self.follow_symlinks,
+            min_depth: self.min_depth,
+            max_depth: self.max_depth,
+            // Deleting on other filesystems would not bring the target closer
+            one_file_system: self.one_file_system || free_target.is_some(),
+            retention,
+            free_target,
+            limits: self.limits,
+            deadline: None,
+            skip_glob_match: match_all,
+            glob_pattern,
+            glob_matcher: globset,
+            exclude_matcher: exclude_glob,
+            regex,
+            exclude_regex,
+            regex_target: self.regex_target,
+            mime: self.mime,
+            uid,
+            gid,
+            perm: self.perm,
+            dir_rules: Arc::new(DirRules::new(rules)),
+            ignore_mode: self.ignore_mode,
+            protected: Arc::new(protected),
+            storage_kind,
+            custom_filters: self.custom_filters,
+        };
+
+        let dated_retention = config
+            .retention
+            .as_ref()
+            .is_some_and(|r| r.by == RetainBy::Newest);
+        if config.filters_age() || dated_retention {
+            info!("Ages use {}", config.time_source().describe());
+        }
+
+        Ok(Spacefree {
+            paths: self.paths,
+            config: Arc::new(config),
+            log_path: self.log_path,
+            progress: self.progress.unwrap_or_else(ProgressBar::hidden),
+            clock: RunClock::default(),
+        })
+    }
+}
+
+/// A configured scan/delete run
+pub struct Spacefree {
+    paths: Vec<PathBuf>,
+    config: Arc<DeleteConfig>,
+    log_path: Option<PathBuf>,
+    progress: ProgressBar,
+    /// Working time used so far, against the time limit
+    clock: RunClock,
+}
+
+impl Spacefree {
+    /// Start configuring a run
+    pub fn builder() -> SpacefreeBuilder {
+        SpacefreeBuilder::default()
+    }
+
+    /// Input paths in the order they were given
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Effective delete configuration (storage kind and parallelism resolved)
+    pub fn config(&self) -> &DeleteConfig {
+        &self.config
+    }
+
+    /// Start an operation: its config carries the deadline left by earlier ones.
+    /// Charge the clock with the time since the returned instant once it is done.
+    fn start(&self) -> (Arc<DeleteConfig>, Instant) {
+        let started = Instant::now();
+        let config = match self.clock.deadline(self.config.limits.time_limit) {
+            Some(deadline) => Arc::new(DeleteConfig {
+                deadline: Some(deadline),
+                ..(*self.config).clone()
+            }),
+            None => self.config.clone(),
+        };
+        (config, started)
+    }
+
+    /// Run the scan/delete pipeline
+    pub async fn run(&self) -> Result<DeletionReport, DeleterError> {
+        let (config, started) = self.start();
+        let (directories, individual_files) = split_paths(&self.paths).await;
+        let report = run_deletion_pipeline(
+            directories,
+            individual_files,
+            config,
+            self.progress.clone(),
+            self.log_path.clone(),
+        )
+        .await;
+        self.clock.charge(started.elapsed());
+        report
+    }
+
+    /// Refuse a plan containing protected entries (e.g. one made with protection overridden)
+    pub fn check_plan(&self, plan: &Plan) -> Result<(), DeleterError> {
+        match plan
+            .entries
+            .iter()
+            .find(|e| self.config.protected.contains(&e.path))
+        {
+            Some(entry) => Err(DeleterError::Protected(entry.path.display().to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Delete exactly the entries recorded in `plan`.
+    ///
+    /// Only the delete options (trash, dry run, parallelism, log) of this run are
+    /// used; the selection comes from the plan. With [`DriftPolicy::Abort`] nothing
+    /// is deleted if any entry changed since the plan was created.
+    pub async fn apply(
+        &self,
+        plan: &Plan,
+        policy: DriftPolicy,
+    ) -> Result<DeletionReport, DeleterError> {
+        self.check_plan(plan)?;
+        if policy == DriftPolicy::Abort {
+            let drifted = plan.verify().await;
+            if !drifted.is_empty() {
+                return Err(DeleterError::PlanDrift(drifted.len()));
+            }
+        }
+        let (config, started) = self.start();
+        let report = run_plan_pipeline(
+            plan.clone(),
+            config,
+            self.progress.clone(),
+            self.log_path.clone(),
+        )
+        .await;
+        self.clock.charge(started.elapsed());
+        report
+    }
+
+    /// Delete the redundant copies found by [`Spacefree::duplicates`].
+    ///
+    /// A copy that changed since it was hashed is left in place, and so is every
+    /// copy in a group whose kept file changed.
+    pub async fn dedupe(
+        &self,
+        groups: Vec<DuplicateGroup>,
+    ) -> Result<DeletionReport, DeleterError> {
+        let (config, started) = self.start();
+        let (directories, individual_files) = split_paths(&self.paths).await;
+        let roots = directories.into_iter().chain(individual_files).collect();
+        let report = run_dedupe_pipeline(
+            roots,
+            groups,
+            config,
+            self.progress.clone(),
+            self.log_path.clone(),
+        )
+        .await;
+        self.clock.charge(started.elapsed());
+        report
+    }
+}
+
+impl Spacefree {
+    /// Scan without deleting, streaming every match.
+    ///
+    /// The receiver closes once all roots have been scanned.
+    pub fn scan(&self) -> mpsc::Receiver<ScanResult> {
+        self.scan_keeping(Arc::default())
+    }
+
+    /// [`Spacefree::scan`], adding the matches retention holds back to `kept`
+    fn scan_keeping(&self, kept: Arc<Mutex<Vec<KeptEntry>>>) -> mpsc::Receiver<ScanResult> {
+        let (tx, rx) = mpsc::channel((self.config.parallelism * 8).max(64));
+        let paths = self.paths.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let (directories, individual_files) = split_paths(&paths).await;
+            for handle in spawn_scanners(directories, individual_files, tx, kept, &config) {
+                handle.await.ok();
+            }
+        });
+        rx
+    }
+
+    /// Full scan with every filter applied, without deleting anything.
+    ///
+    /// Cut short, with [`ScanSummary::stopped`] set, if the time limit runs out.
+    pub async fn preview(&self) -> ScanSummary {
+        let (config, started) = self.start();
+        let budget = Budget::new(config.limits, config.deadline);
+        let mut summary = ScanSummary::default();
+        let kept = Arc::default();
+        let mut results = self.scan_keeping(Arc::clone(&kept));
+        loop {
+            match budget.recv(&mut results).await {
+                Ok(Some(result)) => summary.add(&result),
+                Ok(None) => break,
+                Err(limit) => {
+                    summary.stopped = Some(limit);
+                    break;
+                }
+            }
+        }
+        summary.kept = std::mem::take(&mut *kept.lock().unwrap());
+        self.clock.charge(started.elapsed());
+        summary
+    }
+
+    /// Full scan grouping matching files with identical contents, without deleting
+    /// anything, or the time limit if it runs out first
+    pub async fn duplicates(&self, policy: &KeepPolicy) -> Result<Vec<DuplicateGroup>, Limit> {
+        let (config, started) = self.start();
+        let budget = Budget::new(config.limits, config.deadline);
+        let groups = async {
+            let mut files = Vec::new();
+            let mut results = self.scan();
+            while let Some(result) = budget.recv(&mut results).await? {
+                files.push(result);
+            }
+            budget
+                .within(find_duplicates(files, policy, config.parallelism))
+                .await
+        }
+        .await;
+        self.clock.charge(started.elapsed());
+        groups
+    }
+
+    /// Full scan recording every match with the metadata `apply` verifies against
+    pub async fn plan(&self) -> Plan {
+        let (directories, individual_files) = split_paths(&self.paths).await;
+        let roots = directories
+            .iter()
+            .chain(individual_files.iter())
+            .cloned()
+            .collect();
+        let mut plan = Plan::new(self.config.glob_pattern.clone(), roots);
+
+        let (tx, mut rx) = mpsc::channel((self.config.parallelism * 8).max(64));
+        let handles = spawn_scanners(
+            directories,
+            individual_files,
+            tx,
+            Arc::default(),
+            &self.config,
+        );
+        while let Some(result) = rx.recv().await {
+            match PlanEntry::capture(&result).await {
+                Ok(entry) => plan.entries.push(entry),
+                Err(e) => warn!(
+                    "Cannot stat {}, leaving it out: {}",
+                    result.path.display(),
+                    e
+                ),
+            }
+        }
+        for handle in handles {
+            handle.await.ok();
+        }
+        plan
+    }
+}
+
+/// Combine a name filter and a numeric id filter into one id
+fn resolve_id(
+    kind: &str,
+    name: Option<&str>,
+    id: Option<u32>,
+    lookup: fn(&str) -> Result<u32, DeleterError>,
+) -> Result<Option<u32>, DeleterError> {
+    match (name.map(lookup).transpose()?, id) {
+        (Some(resolved), Some(id)) if resolved != id => Err(DeleterError::Config(format!(
+            "{} '{}' has id {}, not {}",
+            kind,
+            name.unwrap_or_default(),
+            resolved,
+            id
+        ))),
+        (resolved, id) => Ok(resolved.or(id)),
+    }
+}
+
+/// Separate directory roots from individual entries, skipping paths that cannot be read
+pub async fn split_paths(paths: &[PathBuf]) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut directories = Vec::new();
+    let mut individual_files = Vec::new();
+
+    for path in paths {
+        match fs::symlink_metadata(path).await {
+            Ok(m) if m.is_dir() => directories.push(path.clone()),
+            // A link to a directory is walked like the directory itself
+            Ok(m) if m.is_symlink() && fs::metadata(path).await.is_ok_and(|t| t.is_dir()) => {
+                directories.push(path.clone())
+            }
+            // Files, other links (dangling ones too), sockets and FIFOs
+            Ok(_) => individual_files.push(path.clone()),
+            Err(e) => warn!("Cannot access path ({}), skipping: {}", e, path.display()),
+        }
+    }
+
+    (directories, individual_files)
+}
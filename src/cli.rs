@@ -1,16 +1,123 @@
+use crate::builder::{Spacefree, SpacefreeBuilder};
+use crate::config::EntryKind;
+use crate::dedupe::{KeepPolicy, KeepRule};
 use crate::error::DeleterError;
-use clap::Parser;
-use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
-use std::path::PathBuf;
+use crate::filter::{RegexTarget, TimeField};
+use crate::gitignore::IgnoreMode;
+use crate::magic::MimePattern;
+use crate::namedate::{NameDate, NameDateFallback};
+use crate::owner::PermMatch;
+use crate::plan::DriftPolicy;
+use crate::restore::ConflictPolicy;
+use crate::settings::Defaults;
+use crate::space::{FreeGoal, FreeOrder};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::RegexSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 /// Command-line interface definition
 #[derive(Parser, Debug)]
 #[command(
     name = "spf",
     about = "🚀 Ultra-fast file deletion CLI tool (supports trash)",
-    version
+    after_help = "`spa <PATHS>...` is shorthand for `spa delete <PATHS>...`; \
+                  use `spa delete <PATH>` for a path named like a subcommand.",
+    override_usage = "spa [OPTIONS] <PATHS>...\n       spa [OPTIONS] <COMMAND>",
+    version,
+    subcommand_negates_reqs = true
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub delete: DeleteArgs,
+
+    /// Verbose output: list every processed file and show info logs
+    #[arg(short, long, global = true, overrides_with = "no_verbose")]
+    pub verbose: bool,
+
+    /// Quiet output, even if the config sets verbose
+    #[arg(long, global = true, overrides_with = "verbose")]
+    pub no_verbose: bool,
+
+    /// Config file [default: ~/.config/spacefree/config.toml]
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Named profile from the config file to apply
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+}
+
+impl Cli {
+    /// Parse `std::env::args`, rejecting delete options given before a subcommand.
+    ///
+    /// Global options (`-v`, `--no-verbose`, `--config`, `--profile`) may appear anywhere; anything
+    /// else placed before a subcommand would otherwise be silently ignored.
+    pub fn parse_args() -> Cli {
+        let matches = Cli::command().get_matches();
+        if matches.subcommand().is_some() {
+            let misplaced = matches.ids().find(|id| {
+                !matches!(id.as_str(), "verbose" | "no_verbose" | "config" | "profile")
+                    && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+            });
+            if let Some(id) = misplaced {
+                let mut cmd = Cli::command();
+                let name = cmd
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == id)
+                    .and_then(|arg| arg.get_long())
+                    .map(|long| format!("--{}", long))
+                    .unwrap_or_else(|| format!("<{}>", id.as_str().to_uppercase()));
+                cmd.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("'{}' must come after the subcommand", name),
+                )
+                .exit();
+            }
+        }
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Subcommand to run, defaulting to `delete` with the top-level arguments
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or(Command::Delete(self.delete))
+    }
+}
+
+/// Subcommands
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Delete matching files (default when no subcommand is given)
+    Delete(DeleteArgs),
+    /// List matching files without deleting anything
+    Scan(SelectArgs),
+    /// Record what `delete` would remove to a plan file for review
+    Plan(PlanArgs),
+    /// Delete exactly the entries recorded in a plan file
+    Apply(ApplyArgs),
+    /// Delete all but one copy of files with identical contents
+    Dedupe(DedupeArgs),
+    /// Restore items trashed by a previous run from its log
+    Restore(RestoreArgs),
+    /// Summarize matching files by count, size and extension
+    Stats(SelectArgs),
+    /// Check platform support, storage detection and trash availability
+    Doctor(DoctorArgs),
+    /// Inspect the configuration file
+    Config(ConfigArgs),
+    /// Inspect built-in cleanup presets
+    Presets(PresetsArgs),
+}
+
+/// Paths and the filters selecting files under them (shared by delete, scan and stats)
+#[derive(Args, Debug, Clone)]
+pub struct SelectArgs {
     /// Paths to scan - can be directories or files to delete (space separated)
     #[arg(required = true, value_name = "PATHS")]
     pub paths: Vec<PathBuf>,
@@ -19,38 +126,584 @@ pub struct Cli {
     #[arg(long, value_name = "FILE")]
     pub path_list_file: Vec<PathBuf>,
 
-    /// Glob pattern for files to delete [default: **/* (all files)]
+    /// Glob pattern for files to delete, repeatable [default: **/* (all files)]
     #[arg(short, long, value_name = "PATTERN")]
-    pub glob: Option<String>,
+    pub glob: Vec<String>,
 
-    /// Glob pattern to exclude
+    /// Glob pattern to exclude (repeatable)
     #[arg(long, value_name = "PATTERN")]
-    pub exclude: Option<String>,
+    pub exclude: Vec<String>,
+
+    /// File with one include glob per line (`#` starts a comment)
+    #[arg(long, value_name = "FILE")]
+    pub include_from: Vec<PathBuf>,
 
-    /// Minimum file size (e.g., 100, 10k, 5M, 2G, 1T)
-    #[arg(long, value_name = "SIZE", default_value = "0", value_parser = parse_size)]
-    pub min_size: u64,
+    /// File with one exclude glob per line (`#` starts a comment)
+    #[arg(long, value_name = "FILE")]
+    pub exclude_from: Vec<PathBuf>,
+
+    /// Regex the file name (or relative path, see --regex-target) must match (repeatable)
+    #[arg(long, value_name = "REGEX")]
+    pub regex: Vec<String>,
+
+    /// Regex for file names (or relative paths) to exclude (repeatable)
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_regex: Vec<String>,
+
+    /// What --regex and --exclude-regex are matched against [default: name]
+    #[arg(long, value_enum, value_name = "TARGET")]
+    pub regex_target: Option<RegexTarget>,
+
+    /// Content type sniffed from the file's first bytes, e.g. application/x-core or image/* (repeatable)
+    #[arg(long, visible_alias = "magic", value_name = "TYPE", value_parser = parse_mime)]
+    pub mime: Vec<MimePattern>,
+
+    /// Cleanup presets for build and cache artifacts (comma separated, see `spa presets list`)
+    #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+    pub preset: Vec<String>,
+
+    /// Minimum file size (e.g., 100, 10k, 5M, 2G, 1T) [default: 0]
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_size: Option<u64>,
 
     /// Maximum file size (e.g., 100, 10k, 5M, 2G, 1T)
     #[arg(long, value_name = "SIZE", value_parser = parse_size)]
     pub max_size: Option<u64>,
 
-    /// Minimum file age (e.g., 1d, 2w, 3m, 1y) - only files older than this will be deleted
+    /// Minimum file age (e.g., 1d, 2w, 3mo, 1y, 1d12h) - only files older than this will be deleted
     #[arg(long, value_name = "AGE", value_parser = parse_age)]
     pub min_age: Option<u64>,
 
-    /// Maximum file age (e.g., 1d, 2w, 3m, 1y) - only files newer than this will be deleted
+    /// Maximum file age (e.g., 30m, 12h, 1d12h) - only files newer than this will be deleted
     #[arg(long, value_name = "AGE", value_parser = parse_age)]
     pub max_age: Option<u64>,
 
-    /// Move to system trash instead of permanent delete
+    /// Only files last changed before this date (YYYY-MM-DD, YYYY-MM-DDTHH:MM, or RFC 3339)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub older_than: Option<SystemTime>,
+
+    /// Only files last changed at or after this date (YYYY-MM-DD, YYYY-MM-DDTHH:MM, or RFC 3339)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub newer_than: Option<SystemTime>,
+
+    /// Timestamp age and date filters measure from [default: mtime]
+    #[arg(long, value_enum, value_name = "FIELD")]
+    pub time_field: Option<TimeField>,
+
+    /// Read ages from a date in the file name: strftime-like (db-%Y-%m-%dT%H%M) or a regex with named captures
+    #[arg(long, value_name = "FORMAT", value_parser = parse_name_date)]
+    pub name_date: Option<NameDate>,
+
+    /// Names without a date: skip (never selected by age, kept by retention) or time-field [default: skip]
+    #[arg(long, value_enum, value_name = "MODE", requires = "name_date")]
+    pub name_date_fallback: Option<NameDateFallback>,
+
+    /// Delete directories as well as files
+    #[arg(long, overrides_with = "no_dirs")]
+    pub dirs: bool,
+
+    /// Leave directories alone, even if the config sets dirs
+    #[arg(long, overrides_with = "dirs")]
+    pub no_dirs: bool,
+
+    /// Entry types to select, comma separated: f file, d dir, l symlink, s socket, p fifo [default: f]
+    #[arg(long = "type", value_enum, value_name = "TYPES", value_delimiter = ',')]
+    pub types: Vec<EntryKind>,
+
+    /// Select symbolic links whose target does not exist (added to --type)
+    #[arg(
+        long,
+        overrides_with = "no_broken_symlinks",
+        conflicts_with = "follow_symlinks"
+    )]
+    pub broken_symlinks: bool,
+
+    /// Do not add broken symbolic links, even if the config sets broken-symlinks
+    #[arg(long, overrides_with = "broken_symlinks")]
+    pub no_broken_symlinks: bool,
+
+    /// Follow symbolic links during directory traversal (disabled by default for safety)
+    #[arg(long, overrides_with = "no_follow_symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Do not follow symbolic links, even if the config sets follow-symlinks
+    #[arg(long, overrides_with = "follow_symlinks")]
+    pub no_follow_symlinks: bool,
+
+    /// Skip entries fewer than N levels below each path (1 = leave the path itself alone)
+    #[arg(long, value_name = "N")]
+    pub min_depth: Option<usize>,
+
+    /// Descend at most N levels below each path (1 = only its direct entries)
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Do not cross into other filesystems (mount points, bind mounts) (Unix)
+    #[arg(long, overrides_with = "no_one_file_system")]
+    pub one_file_system: bool,
+
+    /// Cross into other filesystems, even if the config sets one-file-system
+    #[arg(long, overrides_with = "one_file_system")]
+    pub no_one_file_system: bool,
+
+    /// Keep the N newest matches in each directory (or --keep-group) and delete the rest
+    #[arg(long, value_name = "N", conflicts_with = "keep_largest")]
+    pub keep_newest: Option<usize>,
+
+    /// Keep the N largest matches in each directory (or --keep-group) and delete the rest
+    #[arg(long, value_name = "N")]
+    pub keep_largest: Option<usize>,
+
+    /// Keep the newest match of each of the last N days that have one
+    #[arg(long, value_name = "N", conflicts_with = "keep_largest")]
+    pub keep_daily: Option<usize>,
+
+    /// Keep the newest match of each of the last N ISO weeks that have one
+    #[arg(long, value_name = "N", conflicts_with = "keep_largest")]
+    pub keep_weekly: Option<usize>,
+
+    /// Keep the newest match of each of the last N months that have one
+    #[arg(long, value_name = "N", conflicts_with = "keep_largest")]
+    pub keep_monthly: Option<usize>,
+
+    /// Keep the newest match of each of the last N years that have one
+    #[arg(long, value_name = "N", conflicts_with = "keep_largest")]
+    pub keep_yearly: Option<usize>,
+
+    /// Delete matches, oldest first, only until this much space is available (e.g. 50G) (Unix)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub free_target: Option<u64>,
+
+    /// Delete matches, oldest first, only until this percentage of the filesystem is free (Unix)
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, conflicts_with = "free_target")]
+    pub target_free_percent: Option<f64>,
+
+    /// Order for --free-target/--target-free-percent: oldest, largest [default: oldest]
+    #[arg(long, value_enum, value_name = "ORDER")]
+    pub free_order: Option<FreeOrder>,
+
+    /// Count retention rules per file name glob in each directory, e.g. 'app-*.log' (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub keep_group: Vec<String>,
+
+    /// Only files owned by this user (Unix)
+    #[arg(long, value_name = "NAME", conflicts_with = "uid")]
+    pub user: Option<String>,
+
+    /// Only files owned by this group (Unix)
+    #[arg(long, value_name = "NAME", conflicts_with = "gid")]
+    pub group: Option<String>,
+
+    /// Only files owned by this numeric user id (Unix)
+    #[arg(long, value_name = "UID")]
+    pub uid: Option<u32>,
+
+    /// Only files owned by this numeric group id (Unix)
+    #[arg(long, value_name = "GID")]
+    pub gid: Option<u32>,
+
+    /// Permission bits in octal: 644 exact, -002 all of these bits, /022 any of them (Unix)
+    #[arg(long, value_name = "MODE", allow_hyphen_values = true, value_parser = parse_perm)]
+    pub perm: Option<PermMatch>,
+
+    /// Skip files ignored by .gitignore/.ignore and never descend into .git
+    #[arg(long, overrides_with = "no_gitignore")]
+    pub gitignore: bool,
+
+    /// Ignore .gitignore/.ignore rules, even if the config sets gitignore
+    #[arg(long, overrides_with = "gitignore")]
+    pub no_gitignore: bool,
+
+    /// Select only files git would ignore (.gitignore, .ignore, .git/info/exclude, global excludes)
+    #[arg(long, overrides_with = "no_only_ignored", conflicts_with = "gitignore")]
+    pub only_ignored: bool,
+
+    /// Do not limit selection to ignored files, even if the config sets only-ignored
+    #[arg(long, overrides_with = "only_ignored")]
+    pub no_only_ignored: bool,
+
+    /// Protect a path and everything under it, on top of the built-in list (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub protect: Vec<PathBuf>,
+
+    /// Allow touching protected system paths and the home directory itself
     #[arg(long)]
+    pub allow_protected: bool,
+}
+
+impl SelectArgs {
+    /// Start a builder with these filters applied to the given (collected) paths
+    pub fn to_builder(&self, paths: &[PathBuf]) -> Result<SpacefreeBuilder, DeleterError> {
+        let mut builder = Spacefree::builder()
+            .paths(paths.iter().cloned())
+            .min_size(self.min_size.unwrap_or(0))
+            .dirs(self.dirs)
+            .broken_symlinks(self.broken_symlinks)
+            .follow_symlinks(self.follow_symlinks)
+            .min_depth(self.min_depth.unwrap_or(0))
+            .one_file_system(self.one_file_system)
+            .ignore_mode(self.ignore_mode())
+            .allow_protected(self.allow_protected);
+        for glob in &self.glob {
+            builder = builder.glob(glob);
+        }
+        for file in &self.include_from {
+            for glob in read_pattern_file(file)? {
+                builder = builder.glob(glob);
+            }
+        }
+        for exclude in &self.exclude {
+            builder = builder.exclude(exclude);
+        }
+        for file in &self.exclude_from {
+            for exclude in read_pattern_file(file)? {
+                builder = builder.exclude(exclude);
+            }
+        }
+        for regex in &self.regex {
+            builder = builder.regex(regex);
+        }
+        for regex in &self.exclude_regex {
+            builder = builder.exclude_regex(regex);
+        }
+        if let Some(target) = self.regex_target {
+            builder = builder.regex_target(target);
+        }
+        for pattern in &self.mime {
+            builder = builder.mime(pattern.clone());
+        }
+        if let Some(count) = self.keep_newest {
+            builder = builder.keep_newest(count);
+        }
+        if let Some(count) = self.keep_largest {
+            builder = builder.keep_largest(count);
+        }
+        if let Some(count) = self.keep_daily {
+            builder = builder.keep_daily(count);
+        }
+        if let Some(count) = self.keep_weekly {
+            builder = builder.keep_weekly(count);
+        }
+        if let Some(count) = self.keep_monthly {
+            builder = builder.keep_monthly(count);
+        }
+        if let Some(count) = self.keep_yearly {
+            builder = builder.keep_yearly(count);
+        }
+        for pattern in &self.keep_group {
+            builder = builder.keep_group(pattern);
+        }
+        if let Some(goal) = self.free_goal() {
+            builder = builder.free_target(goal);
+        }
+        if let Some(order) = self.free_order {
+            builder = builder.free_order(order);
+        }
+        if let Some(user) = &self.user {
+            builder = builder.user(user);
+        }
+        if let Some(group) = &self.group {
+            builder = builder.group(group);
+        }
+        if let Some(uid) = self.uid {
+            builder = builder.uid(uid);
+        }
+        if let Some(gid) = self.gid {
+            builder = builder.gid(gid);
+        }
+        if let Some(perm) = self.perm {
+            builder = builder.perm(perm);
+        }
+        for kind in &self.types {
+            builder = builder.entry_type(*kind);
+        }
+        for preset in &self.preset {
+            builder = builder.preset(preset);
+        }
+        for path in &self.protect {
+            builder = builder.protect(path);
+        }
+        if let Some(depth) = self.max_depth {
+            builder = builder.max_depth(depth);
+        }
+        if let Some(max) = self.max_size {
+            builder = builder.max_size(max);
+        }
+        if let Some(age) = self.min_age {
+            builder = builder.min_age(age);
+        }
+        if let Some(age) = self.max_age {
+            builder = builder.max_age(age);
+        }
+        if let Some(date) = self.older_than {
+            builder = builder.older_than(date);
+        }
+        if let Some(date) = self.newer_than {
+            builder = builder.newer_than(date);
+        }
+        if let Some(name_date) = &self.name_date {
+            builder = builder.name_date(name_date.clone());
+        }
+        if let Some(fallback) = self.name_date_fallback {
+            builder = builder.name_date_fallback(fallback);
+        }
+        if let Some(field) = self.time_field {
+            builder = builder.time_field(field);
+        }
+        Ok(builder)
+    }
+
+    /// Ignore-file handling selected by `--gitignore`/`--only-ignored`
+    pub fn ignore_mode(&self) -> IgnoreMode {
+        if self.only_ignored {
+            IgnoreMode::Only
+        } else if self.gitignore {
+            IgnoreMode::Respect
+        } else {
+            IgnoreMode::Off
+        }
+    }
+
+    /// Free-space goal from --free-target or --target-free-percent
+    pub fn free_goal(&self) -> Option<FreeGoal> {
+        self.free_target
+            .map(FreeGoal::Bytes)
+            .or(self.target_free_percent.map(FreeGoal::Percent))
+    }
+
+    /// True if any --keep-* retention rule was given
+    fn has_retention(&self) -> bool {
+        [
+            self.keep_newest,
+            self.keep_largest,
+            self.keep_daily,
+            self.keep_weekly,
+            self.keep_monthly,
+            self.keep_yearly,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+
+    /// Fill options not given on the command line from config defaults
+    pub fn apply_defaults(&mut self, defaults: &Defaults) -> Result<(), DeleterError> {
+        if self.path_list_file.is_empty() {
+            self.path_list_file = defaults.path_list_file.clone().unwrap_or_default();
+        }
+        if self.glob.is_empty() {
+            self.glob = defaults.glob.clone().unwrap_or_default();
+        }
+        if self.exclude.is_empty() {
+            self.exclude = defaults.exclude.clone().unwrap_or_default();
+        }
+        if self.include_from.is_empty() {
+            self.include_from = defaults.include_from.clone().unwrap_or_default();
+        }
+        if self.exclude_from.is_empty() {
+            self.exclude_from = defaults.exclude_from.clone().unwrap_or_default();
+        }
+        if self.regex.is_empty() {
+            self.regex = defaults.regex.clone().unwrap_or_default();
+        }
+        if self.exclude_regex.is_empty() {
+            self.exclude_regex = defaults.exclude_regex.clone().unwrap_or_default();
+        }
+        self.regex_target = self.regex_target.or(defaults.regex_target);
+        if self.mime.is_empty() {
+            for pattern in defaults.mime.iter().flatten() {
+                self.mime.push(
+                    parse_mime(pattern)
+                        .map_err(|e| DeleterError::Config(format!("mime: {}", e)))?,
+                );
+            }
+        }
+        if self.user.is_none() && self.uid.is_none() {
+            self.user = defaults.user.clone();
+            self.uid = defaults.uid;
+        }
+        if self.group.is_none() && self.gid.is_none() {
+            self.group = defaults.group.clone();
+            self.gid = defaults.gid;
+        }
+        if self.perm.is_none() {
+            self.perm = config_value(&defaults.perm, "perm", parse_perm)?;
+        }
+        if self.preset.is_empty() {
+            self.preset = defaults.preset.clone().unwrap_or_default();
+        }
+        if self.min_size.is_none() {
+            self.min_size = config_value(&defaults.min_size, "min-size", parse_size)?;
+        }
+        if self.max_size.is_none() {
+            self.max_size = config_value(&defaults.max_size, "max-size", parse_size)?;
+        }
+        if self.min_age.is_none() {
+            self.min_age = config_value(&defaults.min_age, "min-age", parse_age)?;
+        }
+        if self.max_age.is_none() {
+            self.max_age = config_value(&defaults.max_age, "max-age", parse_age)?;
+        }
+        if self.older_than.is_none() {
+            self.older_than = config_value(&defaults.older_than, "older-than", parse_date)?;
+        }
+        if self.newer_than.is_none() {
+            self.newer_than = config_value(&defaults.newer_than, "newer-than", parse_date)?;
+        }
+        self.time_field = self.time_field.or(defaults.time_field);
+        if self.name_date.is_none() {
+            self.name_date = config_value(&defaults.name_date, "name-date", parse_name_date)?;
+        }
+        self.name_date_fallback = self.name_date_fallback.or(defaults.name_date_fallback);
+        if let Some(protect) = &defaults.protect {
+            self.protect.extend(protect.iter().cloned());
+        }
+        // Config may set one mode; any of the four flags on the command line replaces it
+        if !(self.gitignore || self.no_gitignore || self.only_ignored || self.no_only_ignored) {
+            self.gitignore = defaults.gitignore.unwrap_or(false);
+            self.only_ignored = defaults.only_ignored.unwrap_or(false);
+        }
+        self.dirs = flag_or_default(self.dirs, self.no_dirs, defaults.dirs);
+        if self.types.is_empty() {
+            self.types = defaults.types.clone().unwrap_or_default();
+        }
+        self.broken_symlinks = flag_or_default(
+            self.broken_symlinks,
+            self.no_broken_symlinks,
+            defaults.broken_symlinks,
+        );
+        self.follow_symlinks = flag_or_default(
+            self.follow_symlinks,
+            self.no_follow_symlinks,
+            defaults.follow_symlinks,
+        );
+        self.min_depth = self.min_depth.or(defaults.min_depth);
+        self.max_depth = self.max_depth.or(defaults.max_depth);
+        self.one_file_system = flag_or_default(
+            self.one_file_system,
+            self.no_one_file_system,
+            defaults.one_file_system,
+        );
+        // Retention rules on the command line replace the configured ones as a whole
+        if !self.has_retention() {
+            self.keep_newest = defaults.keep_newest;
+            self.keep_largest = defaults.keep_largest;
+            self.keep_daily = defaults.keep_daily;
+            self.keep_weekly = defaults.keep_weekly;
+            self.keep_monthly = defaults.keep_monthly;
+            self.keep_yearly = defaults.keep_yearly;
+        }
+        if self.keep_group.is_empty() {
+            self.keep_group = defaults.keep_group.clone().unwrap_or_default();
+        }
+        if self.free_target.is_none() && self.target_free_percent.is_none() {
+            self.free_target = config_value(&defaults.free_target, "free-target", parse_size)?;
+            self.target_free_percent = defaults.target_free_percent;
+        }
+        self.free_order = self.free_order.or(defaults.free_order);
+        Ok(())
+    }
+}
+
+/// Read glob patterns from a file: one per line, blank lines and `#` comments ignored
+pub fn read_pattern_file(path: &Path) -> Result<Vec<String>, DeleterError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| DeleterError::Config(format!("{}: {}", path.display(), e)))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Resolve a `--flag`/`--no-flag` pair: the command line wins, then the config, then off
+pub fn flag_or_default(on: bool, off: bool, default: Option<bool>) -> bool {
+    if on || off {
+        on
+    } else {
+        default.unwrap_or(false)
+    }
+}
+
+/// Parse an optional config string with a CLI value parser
+fn config_value<T>(
+    value: &Option<String>,
+    key: &str,
+    parse: fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, DeleterError> {
+    value
+        .as_deref()
+        .map(|v| parse(v).map_err(|e| DeleterError::Config(format!("{}: {}", key, e))))
+        .transpose()
+}
+
+/// Stop conditions for commands that delete; the first one reached ends the run
+#[derive(Args, Debug, Clone, Default)]
+pub struct LimitArgs {
+    /// Stop after deleting this many items
+    #[arg(long, value_name = "N")]
+    pub max_count: Option<u64>,
+
+    /// Delete at most this much (e.g. 10G); items that no longer fit are skipped
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_bytes: Option<u64>,
+
+    /// Stop scanning and deleting after this much working time (e.g. 30m, 1h30m);
+    /// time at the confirmation prompt does not count
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub time_limit: Option<u64>,
+}
+
+impl LimitArgs {
+    /// Fill limits not given on the command line from config defaults
+    pub fn apply_defaults(&mut self, defaults: &Defaults) -> Result<(), DeleterError> {
+        self.max_count = self.max_count.or(defaults.max_count);
+        if self.max_bytes.is_none() {
+            self.max_bytes = config_value(&defaults.max_bytes, "max-bytes", parse_size)?;
+        }
+        if self.time_limit.is_none() {
+            self.time_limit = config_value(&defaults.time_limit, "time-limit", parse_duration)?;
+        }
+        Ok(())
+    }
+
+    /// Set the given limits on a builder
+    pub fn apply(&self, mut builder: SpacefreeBuilder) -> SpacefreeBuilder {
+        if let Some(count) = self.max_count {
+            builder = builder.max_count(count);
+        }
+        if let Some(bytes) = self.max_bytes {
+            builder = builder.max_bytes(bytes);
+        }
+        if let Some(secs) = self.time_limit {
+            builder = builder.time_limit(Duration::from_secs(secs));
+        }
+        builder
+    }
+}
+
+/// Arguments for `spa delete`
+#[derive(Args, Debug, Clone)]
+pub struct DeleteArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Move to system trash instead of permanent delete
+    #[arg(long, overrides_with = "no_trash")]
     pub trash: bool,
 
+    /// Delete permanently, even if the config sets trash
+    #[arg(long, overrides_with = "trash")]
+    pub no_trash: bool,
+
     /// Preview what would be deleted without actually deleting
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_dry_run")]
     pub dry_run: bool,
 
+    /// Actually delete, even if the config sets dry-run
+    #[arg(long, overrides_with = "dry_run")]
+    pub no_dry_run: bool,
+
     /// Skip confirmation prompt
     #[arg(short, long)]
     pub yes: bool,
@@ -59,25 +712,204 @@ pub struct Cli {
     #[arg(long)]
     pub delete_root_dir: bool,
 
-    /// Number of parallel workers (0 = auto-detect based on storage type)
-    #[arg(short, long, default_value_t = 0, value_name = "N")]
-    pub parallelism: usize,
+    /// Number of parallel workers (0 = auto-detect based on storage type) [default: 0]
+    #[arg(short, long, value_name = "N")]
+    pub parallelism: Option<usize>,
+
+    /// Log deleted items to file (use without value for auto-named log, or specify path)
+    #[arg(short, long, value_name = "PATH", default_missing_value = "auto", num_args = 0..=1)]
+    pub log: Option<String>,
+
+    #[command(flatten)]
+    pub limits: LimitArgs,
+}
+
+impl DeleteArgs {
+    /// Fill options not given on the command line from config defaults
+    pub fn apply_defaults(&mut self, defaults: &Defaults) -> Result<(), DeleterError> {
+        self.select.apply_defaults(defaults)?;
+        self.trash = flag_or_default(self.trash, self.no_trash, defaults.trash);
+        self.dry_run = flag_or_default(self.dry_run, self.no_dry_run, defaults.dry_run);
+        self.parallelism = self.parallelism.or(defaults.parallelism);
+        self.log = self.log.take().or_else(|| defaults.log.clone());
+        self.limits.apply_defaults(defaults)
+    }
+}
+
+/// Arguments for `spa plan`
+#[derive(Args, Debug, Clone)]
+pub struct PlanArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Plan file to write (JSON)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+}
+
+/// Arguments for `spa apply`
+#[derive(Args, Debug, Clone)]
+pub struct ApplyArgs {
+    /// Plan file written by `spa plan`
+    #[arg(value_name = "PLAN")]
+    pub plan: PathBuf,
+
+    /// What to do with entries whose size, mtime or inode changed since planning
+    #[arg(long, value_enum, default_value_t = DriftPolicy::Abort)]
+    pub on_drift: DriftPolicy,
+
+    /// Move to system trash instead of permanent delete
+    #[arg(long, overrides_with = "no_trash")]
+    pub trash: bool,
+
+    /// Delete permanently, even if the config sets trash
+    #[arg(long, overrides_with = "trash")]
+    pub no_trash: bool,
+
+    /// Check the plan and show what would be deleted without deleting
+    #[arg(long, overrides_with = "no_dry_run")]
+    pub dry_run: bool,
 
-    /// Show all files to be deleted (verbose mode)
+    /// Actually delete, even if the config sets dry-run
+    #[arg(long, overrides_with = "dry_run")]
+    pub no_dry_run: bool,
+
+    /// Skip confirmation prompt
     #[arg(short, long)]
-    pub verbose: bool,
+    pub yes: bool,
 
-    /// Delete directories as well as files
+    /// Allow applying a plan rooted at the root directory (requires -y as well)
     #[arg(long)]
-    pub dirs: bool,
+    pub delete_root_dir: bool,
 
-    /// Follow symbolic links during directory traversal (disabled by default for safety)
+    /// Protect a path and everything under it, on top of the built-in list (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub protect: Vec<PathBuf>,
+
+    /// Allow touching protected system paths and the home directory itself
     #[arg(long)]
-    pub follow_symlinks: bool,
+    pub allow_protected: bool,
+
+    /// Number of parallel workers (0 = auto-detect based on storage type) [default: 0]
+    #[arg(short, long, value_name = "N")]
+    pub parallelism: Option<usize>,
 
     /// Log deleted items to file (use without value for auto-named log, or specify path)
     #[arg(short, long, value_name = "PATH", default_missing_value = "auto", num_args = 0..=1)]
     pub log: Option<String>,
+
+    #[command(flatten)]
+    pub limits: LimitArgs,
+}
+
+impl ApplyArgs {
+    /// Fill delete options not given on the command line from config defaults
+    pub fn apply_defaults(&mut self, defaults: &Defaults) -> Result<(), DeleterError> {
+        if let Some(protect) = &defaults.protect {
+            self.protect.extend(protect.iter().cloned());
+        }
+        self.trash = flag_or_default(self.trash, self.no_trash, defaults.trash);
+        self.dry_run = flag_or_default(self.dry_run, self.no_dry_run, defaults.dry_run);
+        self.parallelism = self.parallelism.or(defaults.parallelism);
+        self.log = self.log.take().or_else(|| defaults.log.clone());
+        self.limits.apply_defaults(defaults)
+    }
+}
+
+/// Arguments for `spa dedupe`
+#[derive(Args, Debug, Clone)]
+pub struct DedupeArgs {
+    #[command(flatten)]
+    pub delete: DeleteArgs,
+
+    /// Which copy of each duplicate group to keep [default: oldest]
+    #[arg(long, value_enum, value_name = "RULE")]
+    pub keep: Option<KeepRule>,
+
+    /// Prefer keeping copies whose path matches this glob, repeatable (--keep breaks ties)
+    #[arg(long, value_name = "PATTERN")]
+    pub keep_path: Vec<String>,
+}
+
+impl DedupeArgs {
+    /// Fill options not given on the command line from config defaults
+    pub fn apply_defaults(&mut self, defaults: &Defaults) -> Result<(), DeleterError> {
+        self.delete.apply_defaults(defaults)?;
+        self.keep = self.keep.or(defaults.keep);
+        if self.keep_path.is_empty() {
+            self.keep_path = defaults.keep_path.clone().unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    /// Keep policy from --keep and --keep-path
+    pub fn keep_policy(&self) -> Result<KeepPolicy, DeleterError> {
+        let prefer = if self.keep_path.is_empty() {
+            None
+        } else {
+            Some(build_globset(&self.keep_path, &[])?.0)
+        };
+        Ok(KeepPolicy {
+            rule: self.keep.unwrap_or_default(),
+            prefer,
+        })
+    }
+}
+
+/// Arguments for `spa restore`
+#[derive(Args, Debug, Clone)]
+pub struct RestoreArgs {
+    /// Log written by a previous `--trash` run
+    #[arg(value_name = "LOG")]
+    pub log: PathBuf,
+
+    /// What to do when a restore target already exists
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Skip)]
+    pub on_conflict: ConflictPolicy,
+
+    /// Show what would be restored without touching the trash
+    #[arg(long, overrides_with = "no_dry_run")]
+    pub dry_run: bool,
+
+    /// Actually delete, even if the config sets dry-run
+    #[arg(long, overrides_with = "dry_run")]
+    pub no_dry_run: bool,
+}
+
+/// Arguments for `spa doctor`
+#[derive(Args, Debug, Clone)]
+pub struct DoctorArgs {
+    /// Paths to check storage detection for [default: current directory]
+    #[arg(value_name = "PATHS")]
+    pub paths: Vec<PathBuf>,
+}
+
+/// Arguments for `spa config`
+#[derive(Args, Debug, Clone)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+/// `spa config` actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /// Print the effective configuration (file defaults merged with --profile)
+    Show,
+}
+
+/// Arguments for `spa presets`
+#[derive(Args, Debug, Clone)]
+pub struct PresetsArgs {
+    #[command(subcommand)]
+    pub action: PresetsAction,
+}
+
+/// `spa presets` actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum PresetsAction {
+    /// Show what each preset targets
+    List,
 }
 
 /// Parse size string (e.g., "10M", "1G") into bytes
@@ -110,61 +942,211 @@ pub fn parse_size(s: &str) -> Result<u64, String> {
         .ok_or_else(|| "size overflow".to_string())
 }
 
-/// Parse age string (e.g., "1d", "2w") into seconds
+/// Parse a `--perm` mode (e.g. "644", "-002", "/022")
+pub fn parse_perm(s: &str) -> Result<PermMatch, String> {
+    s.parse()
+}
+
+/// Parse an age (e.g., "7d", "1d12h", "3mo") into seconds.
+///
+/// Several number-unit pairs are summed. Units are case-insensitive except
+/// `m` (minutes) and `M` (months); `mo` is the unambiguous month unit.
 pub fn parse_age(s: &str) -> Result<u64, String> {
+    parse_span(
+        s,
+        "age",
+        age_unit,
+        "30s, 5m, 12h, 7d, 2w, 3mo, 1y",
+        "s, m/min, h, d, w, mo/M, y",
+    )
+}
+
+/// Parse a duration (e.g., "30m", "1h30m") into seconds.
+///
+/// Like [`parse_age`] but limited to units up to days, so `30M` is an error
+/// rather than thirty months.
+pub fn parse_duration(s: &str) -> Result<u64, String> {
+    parse_span(
+        s,
+        "duration",
+        |unit| age_unit(unit).filter(|&secs| secs <= 86_400),
+        "45s, 30m, 1h30m, 2d",
+        "s, m/min, h, d",
+    )
+}
+
+/// Sum the number-unit pairs in `s`; `what` names the value in errors
+fn parse_span(
+    s: &str,
+    what: &str,
+    unit_secs: impl Fn(&str) -> Option<u64>,
+    examples: &str,
+    units: &str,
+) -> Result<u64, String> {
     let s = s.trim();
     if s.is_empty() {
-        return Err("age cannot be empty".to_string());
+        return Err(format!("{} cannot be empty", what));
     }
 
-    let (num_part, unit_part) = s
-        .find(|c: char| !c.is_ascii_digit())
-        .map(|i| s.split_at(i))
-        .unwrap_or((s, ""));
+    let mut total: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!(
+                "invalid {} '{}': expected a number before '{}'",
+                what, s, rest
+            ));
+        }
+        let num: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("invalid number in {} '{}'", what, s))?;
+        rest = &rest[digits..];
 
-    let num: u64 = num_part
-        .parse()
-        .map_err(|_| format!("invalid number: {}", num_part))?;
-    let unit = unit_part.trim().to_lowercase();
-
-    let seconds = match unit.as_str() {
-        "s" | "sec" | "second" | "seconds" => num,
-        "m" | "min" | "minute" | "minutes" => num * 60,
-        "h" | "hour" | "hours" => num * 3600,
-        "d" | "day" | "days" => num * 86400,
-        "w" | "week" | "weeks" => num * 604800,
-        "M" | "month" | "months" => num * 2592000,
-        "y" | "year" | "years" => num * 31536000,
-        _ => return Err(format!("invalid age unit: {}", unit_part)),
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = rest[..unit_len].trim();
+        rest = &rest[unit_len..];
+        let scale = unit_secs(unit).ok_or_else(|| {
+            if unit.is_empty() {
+                format!(
+                    "missing unit after {} in {} '{}' (e.g., {})",
+                    num, what, s, examples
+                )
+            } else {
+                format!(
+                    "invalid {} unit '{}' in '{}' (use {})",
+                    what, unit, s, units
+                )
+            }
+        })?;
+
+        total = num
+            .checked_mul(scale)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| format!("{} '{}' is too large", what, s))?;
+    }
+
+    Ok(total)
+}
+
+/// Seconds per age unit (a month is 30 days, a year 365 days)
+fn age_unit(unit: &str) -> Option<u64> {
+    // The only case-sensitive pair
+    match unit {
+        "m" => return Some(60),
+        "M" => return Some(2_592_000),
+        _ => {}
+    }
+    let seconds = match unit.to_lowercase().as_str() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hour" | "hours" => 3_600,
+        "d" | "day" | "days" => 86_400,
+        "w" | "week" | "weeks" => 604_800,
+        "mo" | "month" | "months" => 2_592_000,
+        "y" | "year" | "years" => 31_536_000,
+        _ => return None,
     };
+    Some(seconds)
+}
+
+/// Parse a date cutoff: `YYYY-MM-DD` (local midnight), `YYYY-MM-DDTHH:MM[:SS]`
+/// (local time, `T` or a space) or RFC 3339 with an offset
+pub fn parse_date(s: &str) -> Result<SystemTime, String> {
+    use chrono::{DateTime, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
 
-    Ok(seconds)
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.into());
+    }
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| {
+        format!(
+            "invalid date '{}' (expected YYYY-MM-DD, YYYY-MM-DDTHH:MM[:SS] or RFC 3339)",
+            s
+        )
+    })?;
+    match Local.from_local_datetime(&naive) {
+        LocalResult::Single(time) => Ok(time.into()),
+        // Repeated hour when clocks go back: take the first occurrence (chrono
+        // orders the pair by offset, not by instant)
+        LocalResult::Ambiguous(a, b) => Ok(a.min(b).into()),
+        LocalResult::None => Err(format!("'{}' does not exist in the local time zone", s)),
+    }
 }
 
-/// Build globset from include/exclude patterns
+/// Parse a percentage such as `20` or `12.5%` (above 0, at most 100)
+pub fn parse_percent(s: &str) -> Result<f64, String> {
+    let number = s.trim().trim_end_matches('%').trim();
+    match number.parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(percent),
+        Ok(_) => Err(format!(
+            "percentage '{}' must be above 0 and at most 100",
+            s
+        )),
+        Err(_) => Err(format!("invalid percentage '{}' (e.g. 20 or 12.5%)", s)),
+    }
+}
+
+/// Parse a `--name-date` format
+pub fn parse_name_date(s: &str) -> Result<NameDate, String> {
+    s.parse()
+}
+
+/// Parse a `--mime` content type pattern
+pub fn parse_mime(s: &str) -> Result<MimePattern, String> {
+    s.parse()
+}
+
+/// Build globsets from include/exclude patterns (no excludes = `None`)
 pub fn build_globset(
-    include: Option<&str>,
-    exclude: &Option<String>,
-) -> Result<(GlobSet, Option<GlobMatcher>), DeleterError> {
-    let mut builder = GlobSetBuilder::new();
-
-    let pattern = include.unwrap_or("**/*");
-    builder.add(Glob::new(pattern).map_err(|e| DeleterError::Glob(e.to_string()))?);
-
-    let globset = builder
-        .build()
-        .map_err(|e| DeleterError::Glob(e.to_string()))?;
-
-    let exclude_matcher = exclude
-        .as_ref()
-        .map(|ex| {
-            Glob::new(ex)
-                .map_err(|e| DeleterError::Glob(e.to_string()))
-                .map(|g| g.compile_matcher())
-        })
-        .transpose()?;
+    include: &[String],
+    exclude: &[String],
+) -> Result<(GlobSet, Option<GlobSet>), DeleterError> {
+    let compile = |patterns: &[String]| -> Result<GlobSet, DeleterError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern).map_err(|e| DeleterError::Glob(e.to_string()))?);
+        }
+        builder
+            .build()
+            .map_err(|e| DeleterError::Glob(e.to_string()))
+    };
+
+    let globset = compile(include)?;
+    let exclude_set = if exclude.is_empty() {
+        None
+    } else {
+        Some(compile(exclude)?)
+    };
 
-    Ok((globset, exclude_matcher))
+    Ok((globset, exclude_set))
+}
+
+/// Compile regex patterns into one set (None when there are none)
+pub fn build_regexset(patterns: &[String]) -> Result<Option<RegexSet>, DeleterError> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    RegexSet::new(patterns)
+        .map(Some)
+        .map_err(|e| DeleterError::Regex(e.to_string()))
 }
 
 /// Format list of directories for display
@@ -208,3 +1190,95 @@ pub fn format_size(size: u64) -> String {
         format!("{f:.2} {}", UNITS[u])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn age_sums_compound_durations() {
+        assert_eq!(parse_age("1d12h"), Ok(129_600));
+        assert_eq!(parse_age("1h30m"), Ok(5_400));
+        assert_eq!(parse_age("2w 1d"), Ok(1_296_000));
+    }
+
+    #[test]
+    fn age_minutes_and_months_differ_by_case() {
+        assert_eq!(parse_age("3m"), Ok(180));
+        assert_eq!(parse_age("3M"), Ok(7_776_000));
+        assert_eq!(parse_age("3mo"), Ok(7_776_000));
+        assert_eq!(parse_age("3MIN"), Ok(180));
+    }
+
+    #[test]
+    fn age_rejects_missing_and_unknown_units() {
+        assert!(parse_age("30").unwrap_err().contains("missing unit"));
+        assert!(parse_age("30q").unwrap_err().contains("invalid age unit"));
+        assert!(parse_age("d").unwrap_err().contains("expected a number"));
+        assert!(parse_age("").is_err());
+    }
+
+    #[test]
+    fn age_rejects_overflow() {
+        assert!(
+            parse_age("600000000000y")
+                .unwrap_err()
+                .contains("too large")
+        );
+        assert!(parse_age("99999999999999999999s").is_err());
+        assert!(
+            parse_age("500000000000y500000000000y")
+                .unwrap_err()
+                .contains("too large")
+        );
+    }
+
+    #[test]
+    fn duration_stops_at_days() {
+        assert_eq!(parse_duration("1h30m"), Ok(5_400));
+        assert_eq!(parse_duration("2d 12h"), Ok(216_000));
+        assert_eq!(parse_duration("90sec"), Ok(90));
+        for long in ["30M", "1w", "3mo", "1y"] {
+            assert!(
+                parse_duration(long)
+                    .unwrap_err()
+                    .contains("invalid duration unit"),
+                "{}",
+                long
+            );
+        }
+        assert!(parse_duration("30").unwrap_err().contains("missing unit"));
+    }
+
+    #[test]
+    fn date_reads_local_and_offset_forms() {
+        crate::pin_local_time_zone();
+        let midnight = Local.with_ymd_and_hms(2026, 3, 14, 0, 0, 0).unwrap();
+        assert_eq!(parse_date("2026-03-14"), Ok(midnight.into()));
+        let evening = Local.with_ymd_and_hms(2026, 3, 14, 18, 30, 0).unwrap();
+        assert_eq!(parse_date("2026-03-14T18:30"), Ok(evening.into()));
+        assert_eq!(parse_date("2026-03-14 18:30:00"), Ok(evening.into()));
+        assert_eq!(
+            parse_date("2026-03-14T17:30:00Z"),
+            parse_date("2026-03-14T18:30:00+01:00")
+        );
+        assert!(parse_date("14/03/2026").is_err());
+    }
+
+    #[test]
+    fn date_rejects_skipped_local_time() {
+        crate::pin_local_time_zone();
+        let err = parse_date("2026-03-29T02:30").unwrap_err();
+        assert!(err.contains("does not exist"), "{}", err);
+    }
+
+    #[test]
+    fn date_takes_first_of_repeated_local_time() {
+        crate::pin_local_time_zone();
+        assert_eq!(
+            parse_date("2026-10-25T02:30"),
+            parse_date("2026-10-25T02:30:00+02:00")
+        );
+    }
+}
@@ -1,10 +1,24 @@
+use crate::filter::{Filter, RegexTarget, TimeField, TimeSource};
+use crate::gitignore::IgnoreMode;
+use crate::limits::RunLimits;
+use crate::magic::MimePattern;
+use crate::namedate::{NameDate, NameDateFallback};
+use crate::owner::{Ownership, PermMatch};
+use crate::preset::DirRules;
+use crate::protect::ProtectedPaths;
+use crate::retention::Retention;
+use crate::space::FreeTarget;
 use crate::storage::StorageKind;
-use globset::{GlobMatcher, GlobSet};
+use globset::GlobSet;
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use std::fs::FileType;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
 /// Configuration for delete operations
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct DeleteConfig {
     pub use_trash: bool,
     pub dry_run: bool,
@@ -13,23 +27,172 @@ pub struct DeleteConfig {
     pub max_size: Option<u64>,
     pub min_age: Option<u64>,
     pub max_age: Option<u64>,
+    /// Absolute cutoffs: the timestamp must be before `older_than` and not before `newer_than`
+    pub older_than: Option<SystemTime>,
+    pub newer_than: Option<SystemTime>,
+    /// Timestamp the age and date filters measure from
+    pub time_field: TimeField,
+    /// Date embedded in file names, used by age filters and retention instead of `time_field`
+    pub name_date: Option<NameDate>,
+    pub name_date_fallback: NameDateFallback,
     pub verbose: bool,
+    /// Select directories (from --dirs or --type d); only empty ones are removed
     pub dirs: bool,
+    /// Entry kinds selected by --type (plus `Dir` when `dirs` is set)
+    pub types: Vec<EntryKind>,
+    /// Also select symbolic links whose target does not exist
+    pub broken_symlinks: bool,
     /// Whether to follow symbolic links (disabled by default for safety)
     pub follow_symlinks: bool,
+    /// Depth limits below each root (the root is depth 0, its entries depth 1)
+    pub min_depth: usize,
+    pub max_depth: Option<usize>,
+    /// Skip entries on a different filesystem than their root (Unix only)
+    pub one_file_system: bool,
+    /// Matches held back from deletion by --keep-newest/--keep-largest
+    pub retention: Option<Retention>,
+    /// Delete matches in order only until this much space is free (Unix only)
+    pub free_target: Option<FreeTarget>,
+    /// Stop conditions for the deletion stream (--max-count, --max-bytes, --time-limit)
+    pub limits: RunLimits,
+    /// When the time limit runs out for the operation in progress, set as it starts
+    pub deadline: Option<Instant>,
+    /// Human-readable description of the include patterns
     pub glob_pattern: String,
     pub glob_matcher: GlobSet,
-    pub exclude_matcher: Option<GlobMatcher>,
-    /// True if glob_pattern is "**/*" (matches all) - allows skipping glob check
+    pub exclude_matcher: Option<GlobSet>,
+    /// Include regexes (any must match), checked on top of the globs
+    pub regex: Option<RegexSet>,
+    pub exclude_regex: Option<RegexSet>,
+    pub regex_target: RegexTarget,
+    /// Content types sniffed from magic bytes (any must match); empty = not checked
+    pub mime: Vec<MimePattern>,
+    /// Owner, group and permission filters (Unix only)
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub perm: Option<PermMatch>,
+    /// Preset directory rules; files inside matched directories are included
+    /// and the directories themselves are removed once empty
+    pub dir_rules: Arc<DirRules>,
+    /// How `.gitignore`/`.ignore` rules shape traversal and selection
+    pub ignore_mode: IgnoreMode,
+    /// Paths that are never deleted (empty when overridden)
+    pub protected: Arc<ProtectedPaths>,
+    /// True if everything is included (default "**/*") - allows skipping glob check
     pub skip_glob_match: bool,
     /// Storage type for adaptive optimization
     pub storage_kind: StorageKind,
+    /// Extra predicates appended to the standard filter chain
+    pub custom_filters: Vec<Arc<dyn Filter>>,
+}
+
+/// Kind of a filesystem entry, as seen without following symbolic links
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    /// Regular file
+    #[value(name = "f", alias = "file")]
+    File,
+    /// Directory
+    #[value(name = "d", alias = "dir")]
+    Dir,
+    /// Symbolic link (the link itself, never its target)
+    #[value(name = "l", alias = "symlink")]
+    Symlink,
+    /// Unix domain socket
+    #[value(name = "s", alias = "socket")]
+    Socket,
+    /// Named pipe
+    #[value(name = "p", alias = "fifo")]
+    Fifo,
+}
+
+impl EntryKind {
+    /// Kind of a file type; `None` for block and character devices
+    pub fn of(file_type: FileType) -> Option<Self> {
+        if file_type.is_file() {
+            return Some(EntryKind::File);
+        }
+        if file_type.is_dir() {
+            return Some(EntryKind::Dir);
+        }
+        if file_type.is_symlink() {
+            return Some(EntryKind::Symlink);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_socket() {
+                return Some(EntryKind::Socket);
+            }
+            if file_type.is_fifo() {
+                return Some(EntryKind::Fifo);
+            }
+        }
+        None
+    }
+
+    /// Listing suffix, as in `ls -F`
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            EntryKind::File => "",
+            EntryKind::Dir => "/",
+            EntryKind::Symlink => "@",
+            EntryKind::Socket => "=",
+            EntryKind::Fifo => "|",
+        }
+    }
 }
 
 /// Result from scanning a file or directory
 #[derive(Clone)]
 pub struct ScanResult {
     pub path: PathBuf,
-    pub is_dir: bool,
+    pub kind: EntryKind,
+    /// Bytes freed by deleting the entry (0 for anything but regular files)
     pub size: u64,
+    /// Index of the input path this result was found under
+    pub root: usize,
+    /// Owner and mode, recorded when filtering by them so listings can show why
+    pub owner: Option<Ownership>,
+}
+
+impl ScanResult {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+}
+
+impl DeleteConfig {
+    /// True if entries of this kind are selected by --type
+    pub fn selects(&self, kind: EntryKind) -> bool {
+        self.types.contains(&kind)
+    }
+
+    /// Where age filters and retention read each entry's time from
+    pub fn time_source(&self) -> TimeSource {
+        TimeSource {
+            field: self.time_field,
+            name_date: self.name_date.clone(),
+            fallback: self.name_date_fallback,
+        }
+    }
+
+    /// True if matches are selected from the finished scan rather than streamed
+    pub fn buffers_selection(&self) -> bool {
+        self.retention.is_some() || self.free_target.is_some()
+    }
+
+    /// True if any age or date filter is set
+    pub fn filters_age(&self) -> bool {
+        self.min_age.is_some()
+            || self.max_age.is_some()
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+    }
+
+    /// True if any of --user/--group/--uid/--gid/--perm is set
+    pub fn filters_ownership(&self) -> bool {
+        self.uid.is_some() || self.gid.is_some() || self.perm.is_some()
+    }
 }
@@ -0,0 +1,403 @@
+use crate::config::{EntryKind, ScanResult};
+use crate::plan::PlanEntry;
+use futures::stream::{self, StreamExt};
+use globset::GlobSet;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use tokio::task::spawn_blocking;
+use tracing::{debug, warn};
+
+/// Bytes hashed from the start of each same-size file before hashing whole files
+const PARTIAL_LEN: u64 = 16 * 1024;
+
+/// Which copy of a duplicate group is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeepRule {
+    /// Oldest modification time
+    #[default]
+    Oldest,
+    /// Newest modification time
+    Newest,
+    /// Shortest path (fewest characters)
+    ShortestPath,
+}
+
+/// How the surviving copy is chosen: copies matching `prefer` first, then `rule`
+#[derive(Debug, Clone, Default)]
+pub struct KeepPolicy {
+    pub rule: KeepRule,
+    /// Paths to keep in preference to the others, if any copy matches
+    pub prefer: Option<GlobSet>,
+}
+
+impl KeepPolicy {
+    /// Index of the copy to keep; ties go to the lexicographically first path
+    fn pick(&self, copies: &[PlanEntry]) -> usize {
+        let preferred: Vec<usize> = match &self.prefer {
+            Some(set) => (0..copies.len())
+                .filter(|&i| set.is_match(&copies[i].path))
+                .collect(),
+            None => Vec::new(),
+        };
+        let pool = if preferred.is_empty() {
+            (0..copies.len()).collect()
+        } else {
+            preferred
+        };
+        pool.into_iter()
+            .min_by(|&a, &b| {
+                self.compare(&copies[a], &copies[b])
+                    .then_with(|| copies[a].path.cmp(&copies[b].path))
+            })
+            .unwrap_or(0)
+    }
+
+    /// `Less` if `a` is the better copy to keep
+    fn compare(&self, a: &PlanEntry, b: &PlanEntry) -> Ordering {
+        match self.rule {
+            // Unknown times sort last either way
+            KeepRule::Oldest => match (a.modified, b.modified) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (x, y) => x.is_none().cmp(&y.is_none()),
+            },
+            KeepRule::Newest => match (a.modified, b.modified) {
+                (Some(x), Some(y)) => y.cmp(&x),
+                (x, y) => x.is_none().cmp(&y.is_none()),
+            },
+            KeepRule::ShortestPath => a.path.as_os_str().len().cmp(&b.path.as_os_str().len()),
+        }
+    }
+}
+
+/// Files with identical contents: one is kept, the rest are redundant
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// Size of each copy in bytes
+    pub size: u64,
+    /// BLAKE3 hash of the contents, hex encoded
+    pub hash: String,
+    pub keep: PlanEntry,
+    pub remove: Vec<PlanEntry>,
+}
+
+impl DuplicateGroup {
+    /// Bytes freed by removing the redundant copies
+    pub fn redundant_bytes(&self) -> u64 {
+        self.size * self.remove.len() as u64
+    }
+}
+
+/// Group scanned files by identical contents and choose the copy to keep in each.
+///
+/// Files are compared by size, then by a hash of their first bytes, then by a
+/// hash of the whole file, so most files are never read in full. Empty files,
+/// non-files and extra hard links to the same file are ignored.
+pub async fn find_duplicates(
+    results: Vec<ScanResult>,
+    policy: &KeepPolicy,
+    parallelism: usize,
+) -> Vec<DuplicateGroup> {
+    let parallelism = parallelism.max(1);
+
+    let mut by_size: HashMap<u64, Vec<ScanResult>> = HashMap::new();
+    let mut seen_paths = HashSet::new();
+    for result in results {
+        // Overlapping roots yield the same path twice; it must never look like its own copy
+        if result.kind == EntryKind::File
+            && result.size > 0
+            && seen_paths.insert(result.path.clone())
+        {
+            by_size.entry(result.size).or_default().push(result);
+        }
+    }
+    let candidates: Vec<ScanResult> = by_size
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .flatten()
+        .collect();
+
+    // Current metadata, so deletion can tell if a copy changed after hashing
+    let mut entries: Vec<PlanEntry> = stream::iter(candidates)
+        .map(|result| async move {
+            match PlanEntry::capture(&result).await {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!("Cannot stat {}, skipping: {}", result.path.display(), e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(parallelism)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    // Hard links share their data; removing one frees nothing. Inode numbers
+    // are only unique within a filesystem, so the device is part of the key.
+    let mut seen_inodes = HashSet::new();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.retain(|entry| match (entry.device, entry.inode) {
+        (Some(device), Some(inode)) if !seen_inodes.insert((device, inode)) => {
+            debug!("Ignoring hard link {}", entry.path.display());
+            false
+        }
+        _ => true,
+    });
+
+    let mut groups = Vec::new();
+    for (hash, copies) in regroup(entries, Some(PARTIAL_LEN), parallelism).await {
+        if copies[0].size <= PARTIAL_LEN {
+            // The partial hash already covered the whole file
+            groups.push((hash, copies));
+        } else {
+            groups.extend(regroup(copies, None, parallelism).await);
+        }
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .map(|(hash, mut copies)| {
+            copies.sort_by(|a, b| a.path.cmp(&b.path));
+            let keep = copies.remove(policy.pick(&copies));
+            DuplicateGroup {
+                size: keep.size,
+                hash,
+                keep,
+                remove: copies,
+            }
+        })
+        .collect();
+    // Largest savings first
+    duplicates.sort_by(|a, b| {
+        b.redundant_bytes()
+            .cmp(&a.redundant_bytes())
+            .then_with(|| a.keep.path.cmp(&b.keep.path))
+    });
+    duplicates
+}
+
+/// Split same-size files into groups of two or more with equal hashes,
+/// hashing at most `limit` bytes of each
+async fn regroup(
+    entries: Vec<PlanEntry>,
+    limit: Option<u64>,
+    parallelism: usize,
+) -> Vec<(String, Vec<PlanEntry>)> {
+    let hashed: Vec<(PlanEntry, String)> = stream::iter(entries)
+        .map(|entry| async move {
+            let path = entry.path.clone();
+            match spawn_blocking(move || hash_file(&path, limit)).await {
+                Ok(Ok(hash)) => Some((entry, hash)),
+                Ok(Err(e)) => {
+                    warn!("Cannot read {}, skipping: {}", entry.path.display(), e);
+                    None
+                }
+                Err(_) => None,
+            }
+        })
+        .buffer_unordered(parallelism)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let mut by_hash: HashMap<(u64, String), Vec<PlanEntry>> = HashMap::new();
+    for (entry, hash) in hashed {
+        by_hash.entry((entry.size, hash)).or_default().push(entry);
+    }
+    by_hash
+        .into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|((_, hash), copies)| (hash, copies))
+        .collect()
+}
+
+/// BLAKE3 of the first `limit` bytes (or all) of a file, hex encoded
+fn hash_file(path: &Path, limit: Option<u64>) -> io::Result<String> {
+    let file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    match limit {
+        Some(limit) => hasher.update_reader(file.take(limit))?,
+        None => hasher.update_reader(file)?,
+    };
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use globset::{Glob, GlobSetBuilder};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn scanned(paths: &[PathBuf]) -> Vec<ScanResult> {
+        paths
+            .iter()
+            .map(|path| ScanResult {
+                path: path.clone(),
+                kind: EntryKind::File,
+                size: fs::metadata(path).unwrap().len(),
+                root: 0,
+                owner: None,
+            })
+            .collect()
+    }
+
+    fn write(dir: &Path, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn paths(entries: &[PlanEntry]) -> Vec<&Path> {
+        entries.iter().map(|e| e.path.as_path()).collect()
+    }
+
+    #[tokio::test]
+    async fn same_size_files_are_split_by_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let files = vec![
+            write(d, "a.txt", b"hello"),
+            write(d, "b.txt", b"hello"),
+            write(d, "c.txt", b"world"),
+            write(d, "d.txt", b"hello!"),
+            write(d, "e.txt", b""),
+            write(d, "f.txt", b""),
+        ];
+        let groups = find_duplicates(scanned(&files), &KeepPolicy::default(), 2).await;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, 5);
+        assert_eq!(groups[0].keep.path, files[0]);
+        assert_eq!(paths(&groups[0].remove), [files[1].as_path()]);
+        assert_eq!(groups[0].redundant_bytes(), 5);
+    }
+
+    #[tokio::test]
+    async fn large_files_are_compared_past_the_partial_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let mut content = vec![7u8; PARTIAL_LEN as usize + 10];
+        let same = [write(d, "a.bin", &content), write(d, "b.bin", &content)];
+        *content.last_mut().unwrap() = 8;
+        let differs_at_end = write(d, "c.bin", &content);
+
+        let mut files = same.to_vec();
+        files.push(differs_at_end);
+        let groups = find_duplicates(scanned(&files), &KeepPolicy::default(), 2).await;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep.path, same[0]);
+        assert_eq!(paths(&groups[0].remove), [same[1].as_path()]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hard_links_and_repeated_paths_are_not_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let original = write(d, "a.txt", b"hello");
+        let link = d.join("b.txt");
+        fs::hard_link(&original, &link).unwrap();
+        let files = vec![original.clone(), link, original.clone()];
+        assert!(
+            find_duplicates(scanned(&files), &KeepPolicy::default(), 2)
+                .await
+                .is_empty()
+        );
+
+        let copy = write(d, "c.txt", b"hello");
+        let mut files = files;
+        files.push(copy.clone());
+        let groups = find_duplicates(scanned(&files), &KeepPolicy::default(), 2).await;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep.path, original);
+        assert_eq!(paths(&groups[0].remove), [copy.as_path()]);
+    }
+
+    fn entry(path: &str, age_secs: Option<u64>) -> PlanEntry {
+        PlanEntry {
+            path: PathBuf::from(path),
+            kind: EntryKind::File,
+            size: 5,
+            modified: age_secs.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            inode: None,
+            device: None,
+            root: 0,
+        }
+    }
+
+    fn kept(policy: &KeepPolicy, copies: &[PlanEntry]) -> PathBuf {
+        copies[policy.pick(copies)].path.clone()
+    }
+
+    fn rule(rule: KeepRule) -> KeepPolicy {
+        KeepPolicy { rule, prefer: None }
+    }
+
+    #[test]
+    fn keep_rules_pick_by_time_or_path_length() {
+        let copies = [
+            entry("/data/photos/b.jpg", Some(200)),
+            entry("/data/a.jpg", Some(300)),
+            entry("/data/backup/old/c.jpg", Some(100)),
+            entry("/data/unknown.jpg", None),
+        ];
+        assert_eq!(
+            kept(&rule(KeepRule::Oldest), &copies),
+            Path::new("/data/backup/old/c.jpg")
+        );
+        assert_eq!(
+            kept(&rule(KeepRule::Newest), &copies),
+            Path::new("/data/a.jpg")
+        );
+        assert_eq!(
+            kept(&rule(KeepRule::ShortestPath), &copies),
+            Path::new("/data/a.jpg")
+        );
+    }
+
+    #[test]
+    fn ties_and_unknown_times_are_settled_by_path() {
+        let copies = [
+            entry("/b/x", None),
+            entry("/b/y", Some(100)),
+            entry("/a/y", Some(100)),
+        ];
+        // Unknown times lose under either time rule
+        assert_eq!(kept(&rule(KeepRule::Oldest), &copies), Path::new("/a/y"));
+        assert_eq!(kept(&rule(KeepRule::Newest), &copies), Path::new("/a/y"));
+        assert_eq!(
+            kept(&rule(KeepRule::ShortestPath), &copies),
+            Path::new("/a/y")
+        );
+    }
+
+    #[test]
+    fn preferred_copies_win_when_any_match() {
+        let mut set = GlobSetBuilder::new();
+        set.add(Glob::new("/archive/**").unwrap());
+        let policy = KeepPolicy {
+            rule: KeepRule::Oldest,
+            prefer: Some(set.build().unwrap()),
+        };
+        let copies = [
+            entry("/home/a.jpg", Some(100)),
+            entry("/archive/2024/a.jpg", Some(300)),
+            entry("/archive/a.jpg", Some(200)),
+        ];
+        // The rule still decides among the preferred copies
+        assert_eq!(kept(&policy, &copies), Path::new("/archive/a.jpg"));
+        // And among all of them when none is preferred
+        assert_eq!(kept(&policy, &copies[..1]), Path::new("/home/a.jpg"));
+        let elsewhere = [
+            entry("/home/b.jpg", Some(300)),
+            entry("/tmp/b.jpg", Some(100)),
+        ];
+        assert_eq!(kept(&policy, &elsewhere), Path::new("/tmp/b.jpg"));
+    }
+}
@@ -1,15 +1,22 @@
-use crate::config::{DeleteConfig, ScanResult};
+use crate::config::{DeleteConfig, EntryKind, ScanResult};
+use crate::dedupe::DuplicateGroup;
 use crate::error::DeleterError;
+use crate::limits::{Budget, Limit};
 use crate::log::DeletedItem;
-use crate::scan::{scan_files_direct, scan_to_channel};
+use crate::plan::{Drift, DriftedItem, Plan, PlanEntry};
+use crate::report::{Counters, DeletionReport, FailedItem, FreeSpaceReport, StopReport};
+use crate::scan::spawn_scanners;
 use indicatif::ProgressBar;
 use std::path::PathBuf;
-use std::sync::{
-    Arc,
-    atomic::{AtomicU64, Ordering},
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    sync::mpsc::{self, error::TryRecvError},
+    task::{JoinHandle, spawn_blocking},
 };
-use tokio::{fs, io::AsyncWriteExt, sync::mpsc, task::spawn_blocking};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use trash::delete as trash_delete;
 
 /// Run the deletion pipeline with streaming scan and delete
@@ -19,30 +26,173 @@ pub async fn run_deletion_pipeline(
     config: Arc<DeleteConfig>,
     pb: ProgressBar,
     log_path: Option<PathBuf>,
-) -> Result<(u64, u64, u64, Vec<PathBuf>), DeleterError> {
-    // Channels for streaming pipeline - size tuned based on parallelism
-    let channel_capacity = (config.parallelism * 8).max(64);
-    let (scan_tx, mut scan_rx) = mpsc::channel::<ScanResult>(channel_capacity);
+) -> Result<DeletionReport, DeleterError> {
+    // Every input path is a root; individual files come after directories
+    let roots: Vec<PathBuf> = directories
+        .iter()
+        .chain(individual_files.iter())
+        .cloned()
+        .collect();
+
+    let (scan_tx, scan_rx) = mpsc::channel::<ScanResult>(channel_capacity(&config));
+    let kept = Arc::default();
+    let scan_handles = spawn_scanners(
+        directories,
+        individual_files,
+        scan_tx,
+        Arc::clone(&kept),
+        &config,
+    );
+
+    let mut report = delete_stream(roots, scan_rx, scan_handles, config, pb, log_path).await?;
+    report.kept = std::mem::take(&mut *kept.lock().unwrap());
+    Ok(report)
+}
+
+/// Delete exactly the entries of a plan, re-checking each one just before it is queued.
+///
+/// Entries that changed since the plan was created are skipped and listed in
+/// [`DeletionReport::drifted`].
+pub async fn run_plan_pipeline(
+    plan: Plan,
+    config: Arc<DeleteConfig>,
+    pb: ProgressBar,
+    log_path: Option<PathBuf>,
+) -> Result<DeletionReport, DeleterError> {
+    let batches = vec![Batch {
+        guard: None,
+        entries: plan.entries,
+    }];
+    run_batches(plan.roots, batches, config, pb, log_path).await
+}
+
+/// Delete the redundant copies of each duplicate group.
+///
+/// Each copy is re-checked like a plan entry, and a group is left alone entirely
+/// if the copy being kept changed since it was hashed.
+pub async fn run_dedupe_pipeline(
+    roots: Vec<PathBuf>,
+    groups: Vec<DuplicateGroup>,
+    config: Arc<DeleteConfig>,
+    pb: ProgressBar,
+    log_path: Option<PathBuf>,
+) -> Result<DeletionReport, DeleterError> {
+    let batches = groups
+        .into_iter()
+        .map(|group| Batch {
+            guard: Some(group.keep),
+            entries: group.remove,
+        })
+        .collect();
+    run_batches(roots, batches, config, pb, log_path).await
+}
+
+/// Recorded entries deleted only while they, and `guard` if set, are unchanged
+struct Batch {
+    guard: Option<PlanEntry>,
+    entries: Vec<PlanEntry>,
+}
+
+/// Delete checked batches in order, reporting skipped entries as drifted
+async fn run_batches(
+    roots: Vec<PathBuf>,
+    batches: Vec<Batch>,
+    config: Arc<DeleteConfig>,
+    pb: ProgressBar,
+    log_path: Option<PathBuf>,
+) -> Result<DeletionReport, DeleterError> {
+    let (scan_tx, scan_rx) = mpsc::channel::<ScanResult>(channel_capacity(&config));
+    let producer = tokio::spawn(async move {
+        let mut drifted = Vec::new();
+        'batches: for batch in batches {
+            let guard_drift = match &batch.guard {
+                Some(guard) => guard.check().await.err().map(|drift| {
+                    warn!("Keeping every copy of {}: {}", guard.path.display(), drift);
+                    Drift::KeptCopyChanged
+                }),
+                None => None,
+            };
+            for entry in batch.entries {
+                if crate::is_shutdown_requested() {
+                    break 'batches;
+                }
+                let checked = match &guard_drift {
+                    Some(drift) => Err(drift.clone()),
+                    None => entry.check().await,
+                };
+                match checked {
+                    Ok(()) => {
+                        if scan_tx.send(entry.to_scan_result()).await.is_err() {
+                            break 'batches;
+                        }
+                    }
+                    Err(drift) => {
+                        warn!("Skipping {}: {}", entry.path.display(), drift);
+                        drifted.push(DriftedItem {
+                            path: entry.path,
+                            root: entry.root,
+                            drift,
+                        });
+                    }
+                }
+            }
+        }
+        drifted
+    });
+
+    let mut report = delete_stream(roots, scan_rx, Vec::new(), config, pb, log_path).await?;
+
+    let drifted = producer.await.map_err(|_| DeleterError::Join)?;
+    report.skipped += drifted.len() as u64;
+    for item in &drifted {
+        if let Some(root) = report.roots.get_mut(item.root) {
+            root.skipped += 1;
+        }
+    }
+    report.drifted = drifted;
+    Ok(report)
+}
+
+/// Channel size tuned based on parallelism
+fn channel_capacity(config: &DeleteConfig) -> usize {
+    (config.parallelism * 8).max(64)
+}
+
+/// Delete everything received on `scan_rx`, counting results per root
+async fn delete_stream(
+    roots: Vec<PathBuf>,
+    mut scan_rx: mpsc::Receiver<ScanResult>,
+    scan_handles: Vec<JoinHandle<()>>,
+    config: Arc<DeleteConfig>,
+    pb: ProgressBar,
+    log_path: Option<PathBuf>,
+) -> Result<DeletionReport, DeleterError> {
+    let started = Instant::now();
+    let dry_run = config.dry_run;
+    let counters = Arc::new(Counters::new(&roots));
+    // Measured before anything is deleted: selection waits for the whole scan
+    let free_target = config.free_target.clone();
+    let free_before = free_target.as_ref().and_then(|t| t.status().ok());
+    let mut budget = Budget::new(config.limits, config.deadline);
+    let stopped: Arc<Mutex<Option<StopReport>>> = Arc::new(Mutex::new(None));
+
+    let channel_capacity = channel_capacity(&config);
     let (deleted_tx, mut deleted_rx) = mpsc::channel::<DeletedItem>(channel_capacity);
-    let (trash_tx, mut trash_rx) = mpsc::channel::<PathBuf>(channel_capacity);
-    let (fail_tx, mut fail_rx) = mpsc::channel::<PathBuf>((config.parallelism * 2).max(16));
+    let (trash_tx, mut trash_rx) = mpsc::channel::<ScanResult>(channel_capacity);
+    let (fail_tx, mut fail_rx) = mpsc::channel::<FailedItem>((config.parallelism * 2).max(16));
     let fail_tx = Arc::new(fail_tx);
 
-    let deleted_count = Arc::new(AtomicU64::new(0));
-    let failed_count = Arc::new(AtomicU64::new(0));
-    let bytes_freed = Arc::new(AtomicU64::new(0));
-
     // Dedicated trash worker thread
-    let deleted_count_trash = deleted_count.clone();
-    let failed_count_trash = failed_count.clone();
+    let counters_trash = counters.clone();
     let deleted_tx_trash = deleted_tx.clone();
     let fail_tx_trash = fail_tx.clone();
-    spawn_blocking(move || {
-        while let Some(path) = trash_rx.blocking_recv() {
+    let trash_handle = spawn_blocking(move || {
+        while let Some(result) = trash_rx.blocking_recv() {
+            let path = result.path;
             match trash_delete(&path) {
                 Ok(_) => {
                     info!("Moved to trash: {}", path.display());
-                    deleted_count_trash.fetch_add(1, Ordering::Relaxed);
+                    counters_trash.deleted(result.root, 0);
                     let _ = deleted_tx_trash.blocking_send(DeletedItem {
                         path: path.clone(),
                         is_dir: false,
@@ -54,8 +204,13 @@ pub async fn run_deletion_pipeline(
                 }
                 Err(e) => {
                     error!("Failed to move to trash {}: {}", path.display(), e);
-                    failed_count_trash.fetch_add(1, Ordering::Relaxed);
-                    let _ = fail_tx_trash.blocking_send(path);
+                    counters_trash.failed(result.root);
+                    let _ = fail_tx_trash.blocking_send(FailedItem {
+                        path,
+                        root: result.root,
+                        kind: std::io::ErrorKind::Other,
+                        message: e.to_string(),
+                    });
                 }
             }
         }
@@ -76,67 +231,116 @@ pub async fn run_deletion_pipeline(
         })
     });
 
-    // Spawn scanner tasks
-    let scan_handles: Vec<_> = directories
-        .into_iter()
-        .map(|root| {
-            let scan_tx = scan_tx.clone();
-            let config = config.clone();
-            tokio::spawn(async move {
-                let _ = scan_to_channel(root, scan_tx, config).await;
-            })
-        })
-        .collect();
-
-    if !individual_files.is_empty() {
-        let scan_tx = scan_tx.clone();
-        let config = config.clone();
-        tokio::spawn(async move {
-            let _ = scan_files_direct(individual_files, scan_tx, config).await;
-        });
-    }
-
-    drop(scan_tx);
-
     // Delete consumer with proper concurrency control using for_each_concurrent
-    let delete_count = deleted_count.clone();
-    let fail_count = failed_count.clone();
-    let total_bytes = bytes_freed.clone();
+    let counters_delete = counters.clone();
     let fail_tx_for_tasks = fail_tx.clone();
     let pb_clone = pb.clone();
+    let stopped_delete = stopped.clone();
     let delete_handle = tokio::spawn(async move {
         use futures::stream::{Stream, StreamExt, TryStreamExt};
 
+        // Real runs re-measure before each item and stop as soon as the goal is reached
+        let goal_reached = {
+            let config = config.clone();
+            move || {
+                !config.dry_run
+                    && config.free_target.as_ref().is_some_and(|target| {
+                        let met = target.met();
+                        if met {
+                            info!("Free space goal reached, stopping deletion stream");
+                        }
+                        met
+                    })
+            }
+        };
+
         // HDD optimization: collect and sort by path for sequential access
         // SSD optimization: stream directly for high parallelism
+        // Free-space targets arrive in deletion order, so they are never re-sorted
         let stream: std::pin::Pin<Box<dyn Stream<Item = ScanResult> + Send>> =
-            if config.storage_kind.should_sort() {
+            if config.storage_kind.should_sort() && config.free_target.is_none() {
                 // HDD: collect all, sort by path, then yield
                 let mut results: Vec<ScanResult> = Vec::new();
-                while let Some(result) = scan_rx.recv().await {
-                    if crate::is_shutdown_requested() {
-                        break;
+                loop {
+                    match budget.recv(&mut scan_rx).await {
+                        Ok(Some(result)) => {
+                            if crate::is_shutdown_requested() {
+                                break;
+                            }
+                            results.push(result);
+                        }
+                        Ok(None) => break,
+                        Err(limit) => {
+                            let pending = std::mem::take(&mut results);
+                            *stopped_delete.lock().unwrap() =
+                                Some(stop_report(limit, pending, &mut scan_rx, &budget));
+                            break;
+                        }
                     }
-                    results.push(result);
                 }
                 // Sort by path for sequential disk access
                 results.sort_by(|a, b| a.path.cmp(&b.path));
                 Box::pin(async_stream::stream! {
-                    for result in results {
+                    let mut results = results.into_iter();
+                    while let Some(result) = results.next() {
+                        if goal_reached() {
+                            break;
+                        }
+                        match budget.admit(result.size) {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                debug!("Over --max-bytes, skipping {}", result.path.display());
+                                continue;
+                            }
+                            Err(limit) => {
+                                let pending = std::iter::once(result).chain(results);
+                                *stopped_delete.lock().unwrap() =
+                                    Some(stop_report(limit, pending, &mut scan_rx, &budget));
+                                break;
+                            }
+                        }
                         yield result;
                     }
+                    report_skipped(&budget, &stopped_delete);
                 })
             } else {
                 // SSD: stream directly
                 Box::pin(async_stream::stream! {
-                    while let Some(result) = scan_rx.recv().await {
+                    loop {
+                        let result = match budget.recv(&mut scan_rx).await {
+                            Ok(Some(result)) => result,
+                            Ok(None) => break,
+                            Err(limit) => {
+                                *stopped_delete.lock().unwrap() =
+                                    Some(stop_report(limit, None, &mut scan_rx, &budget));
+                                break;
+                            }
+                        };
                         // Check for shutdown request
                         if crate::is_shutdown_requested() {
                             info!("Shutdown requested, stopping deletion stream");
                             break;
                         }
+                        if goal_reached() {
+                            break;
+                        }
+                        match budget.admit(result.size) {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                debug!("Over --max-bytes, skipping {}", result.path.display());
+                                continue;
+                            }
+                            Err(limit) => {
+                                *stopped_delete.lock().unwrap() =
+                                    Some(stop_report(limit, Some(result), &mut scan_rx, &budget));
+                                break;
+                            }
+                        }
                         yield result;
                     }
+                    report_skipped(&budget, &stopped_delete);
+                    // Hang up so the scanners stop too
+                    drop(scan_rx);
                 })
             };
 
@@ -147,17 +351,26 @@ pub async fn run_deletion_pipeline(
                 let trash_tx = trash_tx.clone();
                 let pb = pb_clone.clone();
                 let config = config.clone();
-                let delete_count = delete_count.clone();
-                let fail_count = fail_count.clone();
-                let total_bytes = total_bytes.clone();
+                let counters = counters_delete.clone();
 
                 async move {
                     if config.verbose {
-                        pb.println(result.path.display().to_string());
+                        let line = match result.owner {
+                            Some(owner) => format!("{}  {}", owner, result.path.display()),
+                            None => result.path.display().to_string(),
+                        };
+                        // A hidden bar (no terminal, or none given) drops println output,
+                        // so the listing goes to the caller's log subscriber instead
+                        if pb.is_hidden() {
+                            info!("Processing {}", line);
+                        } else {
+                            pb.println(line);
+                        }
                     }
 
-                    let success = if !config.dry_run {
-                        if result.is_dir {
+                    // Ok(true) = deleted, Ok(false) = skipped, Err = failed
+                    let outcome: Result<bool, std::io::Error> = if !config.dry_run {
+                        if result.is_dir() {
                             // Safe directory deletion: only delete if directory is empty
                             // Never use remove_dir_all as it would ignore glob patterns
                             let is_empty = match fs::read_dir(&result.path).await {
@@ -170,8 +383,8 @@ pub async fn run_deletion_pipeline(
                             };
                             if is_empty {
                                 match fs::remove_dir(&result.path).await {
-                                    Ok(_) => true,
-                                    Err(_e) => {
+                                    Ok(_) => Ok(true),
+                                    Err(e) => {
                                         // Check if directory still exists - if not, it was deleted despite the error
                                         let mut still_exists = true;
                                         for _ in 0..3 {
@@ -189,63 +402,68 @@ pub async fn run_deletion_pipeline(
                                                 "Directory deleted despite error: {}",
                                                 result.path.display()
                                             );
-                                            true
+                                            Ok(true)
                                         } else {
                                             error!(
                                                 "Failed to delete directory: {}",
                                                 result.path.display()
                                             );
-                                            false
+                                            Err(e)
                                         }
                                     }
                                 }
                             } else {
                                 // Directory not empty - skip it (files inside will be handled by their own scan entries)
                                 debug!("Skipping non-empty directory: {}", result.path.display());
-                                false
+                                Ok(false)
                             }
                         } else {
+                            // Files, links, sockets and FIFOs: unlinked, never followed
                             if config.use_trash {
                                 // Queue for trash - actual success/failure counted by trash worker
-                                let _ = trash_tx.send(result.path.clone()).await;
+                                let _ = trash_tx.send(result).await;
                                 pb.inc(1);
                                 return Ok::<(), ()>(());
                             } else {
                                 match fs::remove_file(&result.path).await {
                                     Ok(_) => {
                                         info!("Deleted: {}", result.path.display());
-                                        true
+                                        Ok(true)
                                     }
                                     Err(e) => {
                                         error!("Failed to delete {}: {}", result.path.display(), e);
-                                        false
+                                        Err(e)
                                     }
                                 }
                             }
                         }
                     } else {
-                        true // Dry run: pretend everything succeeded
+                        Ok(true) // Dry run: pretend everything succeeded
                     };
 
-                    if success {
-                        delete_count.fetch_add(1, Ordering::Relaxed);
-                        if !result.is_dir {
-                            total_bytes.fetch_add(result.size, Ordering::Relaxed);
+                    match outcome {
+                        Ok(true) => {
+                            counters.deleted(result.root, result.size);
+                            deleted_tx
+                                .send(DeletedItem {
+                                    path: result.path,
+                                    is_dir: result.kind == EntryKind::Dir,
+                                    deleted_at: std::time::SystemTime::now()
+                                        .duration_since(std::time::UNIX_EPOCH)
+                                        .expect("System time went backwards")
+                                        .as_secs(),
+                                })
+                                .await
+                                .ok();
+                        }
+                        Ok(false) => counters.skipped(result.root),
+                        Err(e) => {
+                            counters.failed(result.root);
+                            fail_tx
+                                .send(FailedItem::from_io(result.path, result.root, &e))
+                                .await
+                                .ok();
                         }
-                        deleted_tx
-                            .send(DeletedItem {
-                                path: result.path,
-                                is_dir: result.is_dir,
-                                deleted_at: std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .expect("System time went backwards")
-                                    .as_secs(),
-                            })
-                            .await
-                            .ok();
-                    } else if !result.is_dir {
-                        fail_count.fetch_add(1, Ordering::Relaxed);
-                        fail_tx.send(result.path).await.ok();
                     }
 
                     pb.inc(1);
@@ -261,22 +479,80 @@ pub async fn run_deletion_pipeline(
         handle.await.ok();
     }
     delete_handle.await.map_err(|_| DeleterError::Join)?;
+    trash_handle.await.map_err(|_| DeleterError::Join)?;
     if let Some(log_handle) = log_handle {
         log_handle.await.map_err(|_| DeleterError::Join)?;
     }
 
     drop(fail_tx);
-    let mut failed_paths = Vec::new();
-    while let Some(path) = fail_rx.recv().await {
-        failed_paths.push(path);
+    let mut failures = Vec::new();
+    while let Some(item) = fail_rx.recv().await {
+        failures.push(item);
     }
 
     pb.finish();
 
-    Ok((
-        deleted_count.load(Ordering::Relaxed),
-        failed_count.load(Ordering::Relaxed),
-        bytes_freed.load(Ordering::Relaxed),
-        failed_paths,
-    ))
+    let counters = Arc::try_unwrap(counters).map_err(|_| DeleterError::Join)?;
+    let mut report = counters.into_report(failures, started.elapsed(), dry_run);
+    if let (Some(target), Some((available_before, goal))) = (free_target, free_before) {
+        let available_after = if dry_run {
+            available_before.saturating_add(report.bytes_freed)
+        } else {
+            target
+                .status()
+                .map_or(available_before, |(available, _)| available)
+        };
+        report.free_space = Some(FreeSpaceReport {
+            goal,
+            available_before,
+            available_after,
+            achieved: available_after >= goal,
+        });
+    }
+    report.stopped = stopped.lock().unwrap().take();
+    Ok(report)
+}
+
+/// Record where a run limit stopped the stream: `pending` plus whatever the scan
+/// had already queued
+fn stop_report(
+    limit: Limit,
+    pending: impl IntoIterator<Item = ScanResult>,
+    scan_rx: &mut mpsc::Receiver<ScanResult>,
+    budget: &Budget,
+) -> StopReport {
+    info!("{} reached, stopping deletion stream", limit);
+    let (mut queued, mut queued_bytes) = (0, 0);
+    for result in pending {
+        queued += 1;
+        queued_bytes += result.size;
+    }
+    let scan_complete = loop {
+        match scan_rx.try_recv() {
+            Ok(result) => {
+                queued += 1;
+                queued_bytes += result.size;
+            }
+            Err(TryRecvError::Empty) => break false,
+            Err(TryRecvError::Disconnected) => break true,
+        }
+    };
+    let (skipped, skipped_bytes) = budget.skipped();
+    StopReport {
+        limit,
+        queued,
+        queued_bytes,
+        skipped,
+        skipped_bytes,
+        scan_complete,
+    }
+}
+
+/// Record the items `--max-bytes` passed over once every match has been seen,
+/// unless the run was stopped some other way
+fn report_skipped(budget: &Budget, stopped: &Mutex<Option<StopReport>>) {
+    let mut stopped = stopped.lock().unwrap();
+    if stopped.is_none() && !crate::is_shutdown_requested() {
+        *stopped = budget.skipped_report();
+    }
 }
@@ -16,4 +16,25 @@ pub enum DeleterError {
 
     #[error("Invalid glob: {0}")]
     Glob(String),
+
+    #[error("Invalid regex: {0}")]
+    Regex(String),
+
+    #[error("Invalid log: {0}")]
+    Log(String),
+
+    #[error("Restore failed: {0}")]
+    Restore(String),
+
+    #[error("Config error: {0}")]
+    Config(String),
+
+    #[error("Protected path: {0} (use --allow-protected to override)")]
+    Protected(String),
+
+    #[error("Invalid plan: {0}")]
+    Plan(String),
+
+    #[error("{0} planned item(s) changed since the plan was created")]
+    PlanDrift(usize),
 }
@@ -0,0 +1,479 @@
+use crate::config::DeleteConfig;
+use crate::magic::{MimePattern, Sniffer};
+use crate::namedate::{NameDate, NameDateFallback};
+use crate::owner::PermMatch;
+use crate::preset::DirRules;
+use globset::GlobSet;
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// A scanned entry presented to filters
+pub struct Candidate<'a> {
+    pub path: &'a Path,
+    /// Input path the entry was found under (the file itself for direct files)
+    pub root: &'a Path,
+    pub metadata: &'a Metadata,
+}
+
+/// A predicate deciding whether a scanned entry is selected for deletion
+pub trait Filter: Send + Sync {
+    /// Short name used in debug logs
+    fn name(&self) -> &'static str;
+
+    /// Return true to keep the candidate in the selection
+    fn matches(&self, candidate: &Candidate) -> bool;
+}
+
+/// Ordered list of filters; a candidate must pass every one.
+///
+/// Cheap metadata checks are placed first so expensive filters only see
+/// candidates that already passed them.
+#[derive(Default, Clone)]
+pub struct FilterChain {
+    filters: Vec<Arc<dyn Filter>>,
+}
+
+impl FilterChain {
+    /// Create an empty chain (matches everything)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a filter to the end of the chain
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.filters.push(Arc::new(filter));
+    }
+
+    /// Build the standard chain from the delete configuration
+    pub fn from_config(config: &DeleteConfig) -> Self {
+        let mut chain = Self::new();
+
+        if config.min_size > 0 || config.max_size.is_some() {
+            chain.push(SizeFilter {
+                min: config.min_size,
+                max: config.max_size,
+            });
+        }
+
+        if config.filters_age() {
+            chain.push(
+                AgeFilter::new(config.min_age, config.max_age, config.time_source())
+                    .dates(config.older_than, config.newer_than),
+            );
+        }
+
+        // Skip glob check if using default "**/*" pattern
+        if !config.skip_glob_match {
+            chain.push(IncludeFilter {
+                globs: config.glob_matcher.clone(),
+                dir_rules: config.dir_rules.clone(),
+            });
+        }
+
+        if let Some(ref exclude) = config.exclude_matcher {
+            chain.push(ExcludeFilter(exclude.clone()));
+        }
+
+        if config.filters_ownership() {
+            chain.push(OwnerFilter {
+                uid: config.uid,
+                gid: config.gid,
+                perm: config.perm,
+            });
+        }
+
+        if config.regex.is_some() || config.exclude_regex.is_some() {
+            chain.push(RegexFilter {
+                include: config.regex.clone(),
+                exclude: config.exclude_regex.clone(),
+                target: config.regex_target,
+            });
+        }
+
+        // Reads file contents, so it only sees candidates every other built-in filter kept
+        if !config.mime.is_empty() {
+            chain.push(MimeFilter::new(config.mime.clone()));
+        }
+
+        chain.filters.extend(config.custom_filters.iter().cloned());
+        chain
+    }
+
+    /// True if the candidate passes every filter
+    pub fn matches(&self, candidate: &Candidate) -> bool {
+        self.filters.iter().all(|f| f.matches(candidate))
+    }
+
+    /// Names of the filters in evaluation order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.filters.iter().map(|f| f.name()).collect()
+    }
+}
+
+/// Keep files whose size lies within `[min, max]`
+pub struct SizeFilter {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl Filter for SizeFilter {
+    fn name(&self) -> &'static str {
+        "size"
+    }
+
+    fn matches(&self, candidate: &Candidate) -> bool {
+        let len = candidate.metadata.len();
+        len >= self.min && self.max.is_none_or(|max| len <= max)
+    }
+}
+
+/// Which timestamp `--min-age`/`--max-age` measure age from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeField {
+    /// Last modification
+    #[default]
+    Mtime,
+    /// Last access (may be coarse or frozen on `noatime`/`relatime` mounts)
+    Atime,
+    /// Last status change (Unix only)
+    Ctime,
+    /// Creation (birth) time, where the platform and filesystem record it
+    Btime,
+}
+
+impl TimeField {
+    /// The timestamp from `metadata`, or `None` if unavailable
+    pub fn of(&self, metadata: &Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Mtime => metadata.modified().ok(),
+            TimeField::Atime => metadata.accessed().ok(),
+            TimeField::Ctime => status_changed(metadata),
+            // std uses statx on Linux, so this works wherever the kernel and filesystem support it
+            TimeField::Btime => metadata.created().ok(),
+        }
+    }
+
+    /// Long name for logs
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TimeField::Mtime => "modification time (mtime)",
+            TimeField::Atime => "access time (atime)",
+            TimeField::Ctime => "status change time (ctime)",
+            TimeField::Btime => "birth time (btime)",
+        }
+    }
+}
+
+impl fmt::Display for TimeField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeField::Mtime => "mtime",
+            TimeField::Atime => "atime",
+            TimeField::Ctime => "ctime",
+            TimeField::Btime => "btime",
+        })
+    }
+}
+
+/// Where age filters and retention read an entry's time from
+#[derive(Debug, Clone, Default)]
+pub struct TimeSource {
+    pub field: TimeField,
+    /// Date embedded in the file name, used in preference to `field`
+    pub name_date: Option<NameDate>,
+    /// Names without a parseable date: skipped, or timed by `field`
+    pub fallback: NameDateFallback,
+}
+
+impl TimeSource {
+    /// Time of the entry at `path`, or `None` if it has none
+    pub fn of(&self, path: &Path, metadata: &Metadata) -> Option<SystemTime> {
+        if let Some(name_date) = &self.name_date {
+            let parsed = name_date.time_of(path);
+            if parsed.is_some() || self.fallback == NameDateFallback::Skip {
+                return parsed;
+            }
+        }
+        self.field.of(metadata)
+    }
+
+    /// Time of the entry at `path`, reading its metadata first
+    pub fn of_path(&self, path: &Path, follow_symlinks: bool) -> Option<SystemTime> {
+        let metadata = if follow_symlinks {
+            std::fs::metadata(path)
+        } else {
+            std::fs::symlink_metadata(path)
+        };
+        self.of(path, &metadata.ok()?)
+    }
+
+    /// Long name for logs
+    pub fn describe(&self) -> String {
+        match (&self.name_date, self.fallback) {
+            (None, _) => self.field.describe().to_string(),
+            (Some(name_date), NameDateFallback::Skip) => {
+                format!("file name date ({})", name_date)
+            }
+            (Some(name_date), NameDateFallback::TimeField) => format!(
+                "file name date ({}), else {}",
+                name_date,
+                self.field.describe()
+            ),
+        }
+    }
+}
+
+impl From<TimeField> for TimeSource {
+    fn from(field: TimeField) -> Self {
+        Self {
+            field,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn status_changed(metadata: &Metadata) -> Option<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let secs = u64::try_from(metadata.ctime()).ok()?;
+    let nanos = u32::try_from(metadata.ctime_nsec()).ok()?;
+    Some(SystemTime::UNIX_EPOCH + std::time::Duration::new(secs, nanos))
+}
+
+/// No status change time; the builder rejects `ctime` off Unix
+#[cfg(not(unix))]
+fn status_changed(_metadata: &Metadata) -> Option<SystemTime> {
+    None
+}
+
+/// Keep files whose age (seconds since the chosen timestamp) lies within `[min, max]`
+/// and whose timestamp lies within `[newer_than, older_than)`
+pub struct AgeFilter {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub older_than: Option<SystemTime>,
+    pub newer_than: Option<SystemTime>,
+    pub source: TimeSource,
+    now: u64,
+    /// Set once a missing timestamp has been reported
+    warned: AtomicBool,
+}
+
+impl AgeFilter {
+    /// Ages are measured against the time the filter is created
+    pub fn new(min: Option<u64>, max: Option<u64>, source: impl Into<TimeSource>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("System time went backwards")
+            .as_secs();
+        Self {
+            min,
+            max,
+            older_than: None,
+            newer_than: None,
+            source: source.into(),
+            now,
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Add absolute date cutoffs
+    pub fn dates(mut self, older_than: Option<SystemTime>, newer_than: Option<SystemTime>) -> Self {
+        self.older_than = older_than;
+        self.newer_than = newer_than;
+        self
+    }
+}
+
+impl Filter for AgeFilter {
+    fn name(&self) -> &'static str {
+        "age"
+    }
+
+    fn matches(&self, candidate: &Candidate) -> bool {
+        // An age that cannot be determined never satisfies an age filter
+        let Some(time) = self.source.of(candidate.path, candidate.metadata) else {
+            if !self.warned.swap(true, Ordering::Relaxed) {
+                warn!(
+                    "No {} for {}; files without it are not selected",
+                    self.source.describe(),
+                    candidate.path.display()
+                );
+            }
+            return false;
+        };
+        if self.older_than.is_some_and(|cutoff| time >= cutoff)
+            || self.newer_than.is_some_and(|cutoff| time < cutoff)
+        {
+            return false;
+        }
+        let secs = time
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(self.now);
+        let age = self.now.saturating_sub(secs);
+        self.min.is_none_or(|min| age >= min) && self.max.is_none_or(|max| age <= max)
+    }
+}
+
+/// Keep files with the given owner, group and permission bits
+pub struct OwnerFilter {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub perm: Option<PermMatch>,
+}
+
+impl Filter for OwnerFilter {
+    fn name(&self) -> &'static str {
+        "owner"
+    }
+
+    #[cfg(unix)]
+    fn matches(&self, candidate: &Candidate) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = candidate.metadata;
+        self.uid.is_none_or(|uid| metadata.uid() == uid)
+            && self.gid.is_none_or(|gid| metadata.gid() == gid)
+            && self.perm.is_none_or(|perm| perm.matches(metadata.mode()))
+    }
+
+    /// No ownership information; the builder rejects these options off Unix
+    #[cfg(not(unix))]
+    fn matches(&self, _candidate: &Candidate) -> bool {
+        false
+    }
+}
+
+/// Keep paths matching an include glob or lying inside a preset directory
+pub struct IncludeFilter {
+    pub globs: GlobSet,
+    pub dir_rules: Arc<DirRules>,
+}
+
+impl Filter for IncludeFilter {
+    fn name(&self) -> &'static str {
+        "include"
+    }
+
+    fn matches(&self, candidate: &Candidate) -> bool {
+        self.globs.is_match(candidate.path) || self.dir_rules.contains(candidate.path)
+    }
+}
+
+/// Drop paths matching any exclude glob
+pub struct ExcludeFilter(pub GlobSet);
+
+impl Filter for ExcludeFilter {
+    fn name(&self) -> &'static str {
+        "exclude"
+    }
+
+    fn matches(&self, candidate: &Candidate) -> bool {
+        !self.0.is_match(candidate.path)
+    }
+}
+
+/// What `--regex`/`--exclude-regex` are matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RegexTarget {
+    /// The file name only
+    #[default]
+    Name,
+    /// The path relative to the scan root, with `/` separators
+    Path,
+}
+
+/// Keep paths matching any include regex and no exclude regex
+pub struct RegexFilter {
+    pub include: Option<RegexSet>,
+    pub exclude: Option<RegexSet>,
+    pub target: RegexTarget,
+}
+
+impl RegexFilter {
+    fn subject<'a>(&self, candidate: &'a Candidate) -> Cow<'a, str> {
+        let name = || {
+            candidate
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default()
+        };
+        match self.target {
+            RegexTarget::Name => name(),
+            RegexTarget::Path => match candidate.path.strip_prefix(candidate.root) {
+                // Direct files are their own root; match their name
+                Ok(relative) if !relative.as_os_str().is_empty() => Cow::Owned(
+                    relative
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                ),
+                _ => name(),
+            },
+        }
+    }
+}
+
+impl Filter for RegexFilter {
+    fn name(&self) -> &'static str {
+        "regex"
+    }
+
+    fn matches(&self, candidate: &Candidate) -> bool {
+        let subject = self.subject(candidate);
+        self.include
+            .as_ref()
+            .is_none_or(|set| set.is_match(&subject))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|set| set.is_match(&subject))
+    }
+}
+
+/// Keep regular files whose content type, sniffed from their first bytes, matches a pattern
+pub struct MimeFilter {
+    pub patterns: Vec<MimePattern>,
+    sniffer: Sniffer,
+}
+
+impl MimeFilter {
+    pub fn new(patterns: Vec<MimePattern>) -> Self {
+        Self {
+            patterns,
+            sniffer: Sniffer::new(),
+        }
+    }
+}
+
+impl Filter for MimeFilter {
+    fn name(&self) -> &'static str {
+        "mime"
+    }
+
+    fn matches(&self, candidate: &Candidate) -> bool {
+        // Reading a FIFO or device could block or have side effects
+        if !candidate.metadata.is_file() {
+            return false;
+        }
+        match self.sniffer.detect(candidate.path) {
+            Ok(mime) => self.patterns.iter().any(|p| p.matches(mime)),
+            Err(e) => {
+                debug!("Cannot sniff {}: {}", candidate.path.display(), e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use tracing::warn;
+
+/// How `.gitignore`/`.ignore` rules affect traversal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IgnoreMode {
+    /// Ignore files are not consulted
+    #[default]
+    Off,
+    /// Skip ignored files and never descend into ignored directories or `.git`
+    Respect,
+    /// Select only the untracked files (and directories) git would ignore
+    Only,
+}
+
+/// Ignore rules that apply inside one directory
+struct DirState {
+    /// Enclosing directory, unless this directory starts a repository
+    parent: Option<Rc<DirState>>,
+    /// Root of the innermost git repository containing this directory, if any
+    repo: Option<PathBuf>,
+    /// The directory itself is ignored, so everything beneath it is too
+    ignored: bool,
+    /// Highest precedence first: `.ignore`, `.gitignore`, `.git/info/exclude`
+    rules: Vec<Gitignore>,
+}
+
+/// Git's ignore rules evaluated lazily per directory during a walk.
+///
+/// Paths passed in must be absolute; rules from ignore files above the scan root
+/// (up to the repository root) apply as well.
+pub struct IgnoreRules {
+    global: Gitignore,
+    dirs: RefCell<HashMap<PathBuf, Rc<DirState>>>,
+    /// Tracked paths per repository root, read from the index on first use;
+    /// `None` if the index could not be read
+    tracked: RefCell<HashMap<PathBuf, Rc<Option<HashSet<PathBuf>>>>>,
+}
+
+impl IgnoreRules {
+    pub fn new() -> Self {
+        let (global, err) = Gitignore::global();
+        if let Some(e) = err {
+            warn!("Cannot read global git excludes: {}", e);
+        }
+        Self {
+            global,
+            dirs: RefCell::default(),
+            tracked: RefCell::default(),
+        }
+    }
+
+    /// True if git would ignore `path`
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        match path.parent() {
+            Some(parent) => self.ignored_in(&self.state(parent), path, is_dir),
+            None => false,
+        }
+    }
+
+    /// True if git tracks `path`, or might: a repository whose index cannot be
+    /// read counts everything in it as tracked
+    pub fn may_be_tracked(&self, path: &Path) -> bool {
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+        let Some(repo) = self.state(parent).repo.clone() else {
+            return false;
+        };
+        let tracked = self
+            .tracked
+            .borrow_mut()
+            .entry(repo.clone())
+            .or_insert_with(|| Rc::new(tracked_files(&repo)))
+            .clone();
+        match tracked.as_ref() {
+            Some(files) => files.contains(path),
+            None => true,
+        }
+    }
+
+    fn ignored_in(&self, dir: &DirState, path: &Path, is_dir: bool) -> bool {
+        if dir.ignored {
+            return true;
+        }
+        let mut level = Some(dir);
+        while let Some(state) = level {
+            for rules in &state.rules {
+                match rules.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            level = state.parent.as_deref();
+        }
+        dir.repo.is_some() && self.global.matched(path, is_dir).is_ignore()
+    }
+
+    fn state(&self, dir: &Path) -> Rc<DirState> {
+        if let Some(state) = self.dirs.borrow().get(dir) {
+            return state.clone();
+        }
+
+        let parent = dir.parent().map(|p| self.state(p));
+        let has_git = dir.join(".git").exists();
+        let repo = if has_git {
+            Some(dir.to_path_buf())
+        } else {
+            parent.as_ref().and_then(|p| p.repo.clone())
+        };
+        let ignored = parent
+            .as_ref()
+            .is_some_and(|p| self.ignored_in(p, dir, true));
+
+        let mut rules = Vec::new();
+        rules.extend(load(dir, &dir.join(".ignore")));
+        if repo.is_some() {
+            rules.extend(load(dir, &dir.join(".gitignore")));
+        }
+        if has_git {
+            rules.extend(load(dir, &dir.join(".git").join("info").join("exclude")));
+        }
+
+        let state = Rc::new(DirState {
+            // A nested repository does not inherit the enclosing repository's rules
+            parent: if has_git { None } else { parent },
+            repo,
+            ignored,
+            rules,
+        });
+        self.dirs
+            .borrow_mut()
+            .insert(dir.to_path_buf(), state.clone());
+        state
+    }
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Absolute paths of the files in the index of the repository at `repo`
+fn tracked_files(repo: &Path) -> Option<HashSet<PathBuf>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["ls-files", "-z"])
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output.stdout,
+        Ok(output) => {
+            warn!(
+                "Cannot list tracked files in {}, treating all as tracked: {}",
+                repo.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        Err(e) => {
+            warn!(
+                "Cannot run git in {}, treating all files as tracked: {}",
+                repo.display(),
+                e
+            );
+            return None;
+        }
+    };
+    Some(
+        output
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| repo.join(index_path(name)))
+            .collect(),
+    )
+}
+
+/// Index entries are raw bytes, `/`-separated
+#[cfg(unix)]
+fn index_path(name: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(name))
+}
+
+#[cfg(not(unix))]
+fn index_path(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).as_ref())
+}
+
+/// Compile an ignore file rooted at `dir`, if it exists
+fn load(dir: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(e) = builder.add(file) {
+        warn!("Invalid ignore file {}: {}", file.display(), e);
+    }
+    match builder.build() {
+        Ok(rules) => Some(rules),
+        Err(e) => {
+            warn!("Invalid ignore rules in {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// A repository ignoring `*.o` and `build/`, with `tracked.o` force-added
+    fn repo() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap().join("repo");
+        fs::create_dir_all(root.join("build")).unwrap();
+        git(&root, &["init", "-q"]);
+        fs::write(root.join(".gitignore"), "*.o\nbuild/\n").unwrap();
+        for file in ["main.c", "junk.o", "tracked.o", "build/out.bin"] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        git(&root, &["add", "-f", "main.c", "tracked.o"]);
+        (dir, root)
+    }
+
+    #[test]
+    fn gitignore_rules_apply_inside_the_repository() {
+        let (_dir, root) = repo();
+        let rules = IgnoreRules::new();
+        assert!(rules.is_ignored(&root.join("junk.o"), false));
+        assert!(rules.is_ignored(&root.join("build"), true));
+        // Beneath an ignored directory, whatever the file is called
+        assert!(rules.is_ignored(&root.join("build/out.bin"), false));
+        assert!(!rules.is_ignored(&root.join("main.c"), false));
+    }
+
+    #[test]
+    fn gitignore_outside_a_repository_is_not_read_but_ignore_is() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join(".gitignore"), "*.o\n").unwrap();
+        fs::write(root.join(".ignore"), "*.tmp\n").unwrap();
+        let rules = IgnoreRules::new();
+        assert!(!rules.is_ignored(&root.join("a.o"), false));
+        assert!(rules.is_ignored(&root.join("a.tmp"), false));
+        assert!(!rules.may_be_tracked(&root.join("a.o")));
+    }
+
+    #[test]
+    fn nested_repositories_do_not_inherit_the_parent_rules() {
+        let (_dir, root) = repo();
+        let nested = root.join("vendor");
+        fs::create_dir(&nested).unwrap();
+        git(&nested, &["init", "-q"]);
+        fs::write(nested.join(".gitignore"), "*.a\n").unwrap();
+        let rules = IgnoreRules::new();
+        assert!(!rules.is_ignored(&nested.join("lib.o"), false));
+        assert!(rules.is_ignored(&nested.join("lib.a"), false));
+        assert!(rules.is_ignored(&root.join("lib.o"), false));
+    }
+
+    #[test]
+    fn tracked_files_come_from_the_index() {
+        let (_dir, root) = repo();
+        let rules = IgnoreRules::new();
+        assert!(rules.may_be_tracked(&root.join("tracked.o")));
+        assert!(rules.may_be_tracked(&root.join("main.c")));
+        assert!(!rules.may_be_tracked(&root.join("junk.o")));
+    }
+
+    #[test]
+    fn unreadable_index_treats_every_file_as_tracked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        // A `.git` file that points nowhere makes `git ls-files` fail
+        fs::write(root.join(".git"), "gitdir: missing\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.o\n").unwrap();
+        let rules = IgnoreRules::new();
+        assert!(rules.is_ignored(&root.join("junk.o"), false));
+        assert!(rules.may_be_tracked(&root.join("junk.o")));
+    }
+}
@@ -0,0 +1,92 @@
+//! spacefree - ultra-fast, storage-aware file deletion (supports trash)
+//!
+//! The `spa`/`spacefree` binaries are thin clients over this library.
+//! Embed it with [`Spacefree::builder`]:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), spacefree::DeleterError> {
+//! let report = spacefree::Spacefree::builder()
+//!     .path("build/cache")
+//!     .glob("**/*.tmp")
+//!     .trash(true)
+//!     .build()?
+//!     .run()
+//!     .await?;
+//! println!("deleted {} items", report.deleted);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Module declarations
+pub mod builder;
+pub mod cli;
+pub mod config;
+pub mod dedupe;
+pub mod delete;
+pub mod error;
+pub mod filter;
+pub mod gitignore;
+pub mod limits;
+pub mod log;
+pub mod magic;
+pub mod namedate;
+pub mod owner;
+pub mod plan;
+pub mod preset;
+pub mod protect;
+pub mod report;
+pub mod restore;
+pub mod retention;
+pub mod scan;
+pub mod settings;
+pub mod space;
+pub mod storage;
+
+// Re-exports for convenience
+pub use builder::{Spacefree, SpacefreeBuilder};
+pub use config::{DeleteConfig, EntryKind, ScanResult};
+pub use dedupe::{DuplicateGroup, KeepPolicy, KeepRule};
+pub use delete::{run_dedupe_pipeline, run_deletion_pipeline, run_plan_pipeline};
+pub use error::DeleterError;
+pub use filter::{Candidate, Filter, FilterChain, RegexTarget, TimeField, TimeSource};
+pub use gitignore::IgnoreMode;
+pub use limits::{Limit, RunLimits};
+pub use log::{DeletedItem, LogMode};
+pub use magic::MimePattern;
+pub use namedate::{NameDate, NameDateFallback};
+pub use owner::{Ownership, PermMatch};
+pub use plan::{Drift, DriftPolicy, DriftedItem, Plan, PlanEntry};
+pub use preset::{PRESETS, Preset};
+pub use protect::ProtectedPaths;
+pub use report::{DeletionReport, FailedItem, FreeSpaceReport, RootReport, StopReport};
+pub use restore::{ConflictPolicy, RestoreReport, restore_from_log};
+pub use retention::{Gfs, GroupPattern, KeptEntry, Period, RetainBy, Retention};
+pub use scan::{ScanSummary, collect_paths};
+pub use settings::{Defaults, Settings};
+pub use space::{FreeGoal, FreeOrder, FreeTarget, FsSpace};
+pub use storage::StorageKind;
+
+/// Global shutdown flag for graceful cancellation
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Check if shutdown has been requested
+pub fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Ask running scans and deletions to stop after their current operations
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Pin local time to Central European rules for tests that read it, so the DST
+/// gap (2026-03-29 02:00-03:00) and repeat (2026-10-25 02:00-03:00) are known
+#[cfg(test)]
+pub(crate) fn pin_local_time_zone() {
+    static PINNED: std::sync::Once = std::sync::Once::new();
+    // SAFETY: every test reading local time calls this first, and `Once` holds
+    // the others back until the variable is set
+    PINNED.call_once(|| unsafe { std::env::set_var("TZ", "CET-1CEST,M3.5.0,M10.5.0/3") });
+}
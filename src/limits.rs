@@ -0,0 +1,273 @@
+use crate::cli::format_size;
+use crate::config::ScanResult;
+use crate::report::StopReport;
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Bounds on a deletion run; the first one reached stops it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    /// Most items to delete (directories and failed attempts count)
+    pub max_count: Option<u64>,
+    /// Most bytes to free; an item that would go over is skipped and smaller ones
+    /// still go, until the limit is used up exactly or the matches run out
+    pub max_bytes: Option<u64>,
+    /// Longest the run may spend working: pre-scans, duplicate searches and
+    /// deletion count, time between them (such as at a confirmation prompt) does not
+    pub time_limit: Option<Duration>,
+}
+
+impl RunLimits {
+    pub fn is_empty(&self) -> bool {
+        self.max_count.is_none() && self.max_bytes.is_none() && self.time_limit.is_none()
+    }
+}
+
+impl fmt::Display for RunLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(count) = self.max_count {
+            parts.push(format!("{} item(s)", count));
+        }
+        if let Some(bytes) = self.max_bytes {
+            parts.push(format_size(bytes));
+        }
+        if let Some(limit) = self.time_limit {
+            parts.push(format_duration(limit));
+        }
+        write!(f, "at most {}", parts.join(", "))
+    }
+}
+
+/// The limit that stopped a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    MaxCount(u64),
+    MaxBytes(u64),
+    TimeLimit(Duration),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::MaxCount(count) => write!(f, "--max-count {}", count),
+            Limit::MaxBytes(bytes) => write!(f, "--max-bytes {}", format_size(*bytes)),
+            Limit::TimeLimit(limit) => write!(f, "--time-limit {}", format_duration(*limit)),
+        }
+    }
+}
+
+/// Compact duration such as `1h30m` or `45s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let mut text = String::new();
+    if hours > 0 {
+        text.push_str(&format!("{}h", hours));
+    }
+    if minutes > 0 {
+        text.push_str(&format!("{}m", minutes));
+    }
+    if seconds > 0 || text.is_empty() {
+        text.push_str(&format!("{}s", seconds));
+    }
+    text
+}
+
+/// Working time a run has used of its time limit, summed over its operations
+#[derive(Debug, Default)]
+pub(crate) struct RunClock {
+    spent: Mutex<Duration>,
+}
+
+impl RunClock {
+    /// When an operation starting now must stop, from what is left of `limit`
+    pub(crate) fn deadline(&self, limit: Option<Duration>) -> Option<Instant> {
+        let spent = *self.spent.lock().unwrap();
+        limit.map(|limit| Instant::now() + limit.saturating_sub(spent))
+    }
+
+    /// Count an operation's working time against the limit
+    pub(crate) fn charge(&self, elapsed: Duration) {
+        *self.spent.lock().unwrap() += elapsed;
+    }
+}
+
+/// What one run has used of its limits
+pub(crate) struct Budget {
+    limits: RunLimits,
+    deadline: Option<Instant>,
+    count: u64,
+    bytes: u64,
+    /// Items passed over because they did not fit under `max_bytes`
+    skipped: u64,
+    skipped_bytes: u64,
+}
+
+impl Budget {
+    /// Start counting; the time limit runs out at `deadline`
+    pub(crate) fn new(limits: RunLimits, deadline: Option<Instant>) -> Self {
+        Self {
+            limits,
+            deadline,
+            count: 0,
+            bytes: 0,
+            skipped: 0,
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Finish `work`, or give up with the time limit if it runs out first
+    pub(crate) async fn within<T>(&self, work: impl Future<Output = T>) -> Result<T, Limit> {
+        match (self.deadline, self.limits.time_limit) {
+            (Some(deadline), Some(limit)) => tokio::time::timeout_at(deadline.into(), work)
+                .await
+                .map_err(|_| Limit::TimeLimit(limit)),
+            _ => Ok(work.await),
+        }
+    }
+
+    /// Next scan result, `Ok(None)` once the scan is done, or the time limit if it runs out first
+    pub(crate) async fn recv(
+        &self,
+        scan_rx: &mut mpsc::Receiver<ScanResult>,
+    ) -> Result<Option<ScanResult>, Limit> {
+        self.within(scan_rx.recv()).await
+    }
+
+    /// The time limit, once the deadline has passed
+    pub(crate) fn expired(&self) -> Option<Limit> {
+        match (self.deadline, self.limits.time_limit) {
+            (Some(deadline), Some(limit)) if Instant::now() >= deadline => {
+                Some(Limit::TimeLimit(limit))
+            }
+            _ => None,
+        }
+    }
+
+    /// Count an item of `size` bytes against the limits: `Ok(false)` if it is skipped
+    /// for not fitting under `max_bytes`, or the limit that ends the run
+    pub(crate) fn admit(&mut self, size: u64) -> Result<bool, Limit> {
+        if let Some(limit) = self.expired() {
+            return Err(limit);
+        }
+        if let Some(max) = self.limits.max_count {
+            if self.count >= max {
+                return Err(Limit::MaxCount(max));
+            }
+        }
+        if let Some(max) = self.limits.max_bytes {
+            if self.bytes.saturating_add(size) > max {
+                // Smaller items may still fit, unless nothing is left at all
+                if self.bytes >= max {
+                    return Err(Limit::MaxBytes(max));
+                }
+                self.skipped += 1;
+                self.skipped_bytes += size;
+                return Ok(false);
+            }
+        }
+        self.count += 1;
+        self.bytes += size;
+        Ok(true)
+    }
+
+    /// Items skipped for `max_bytes` so far, and their size
+    pub(crate) fn skipped(&self) -> (u64, u64) {
+        (self.skipped, self.skipped_bytes)
+    }
+
+    /// Report for a run that saw every match but had to skip some for `max_bytes`
+    pub(crate) fn skipped_report(&self) -> Option<StopReport> {
+        let max = self.limits.max_bytes?;
+        (self.skipped > 0).then_some(StopReport {
+            limit: Limit::MaxBytes(max),
+            queued: 0,
+            queued_bytes: 0,
+            skipped: self.skipped,
+            skipped_bytes: self.skipped_bytes,
+            scan_complete: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: Option<Duration> = Some(Duration::from_secs(60));
+
+    /// Time from now until `deadline`, to the second
+    fn left(deadline: Option<Instant>) -> u64 {
+        let left = deadline.unwrap().saturating_duration_since(Instant::now());
+        (left.as_millis() as f64 / 1000.0).round() as u64
+    }
+
+    #[test]
+    fn deadlines_start_when_the_operation_does() {
+        let clock = RunClock::default();
+        assert_eq!(clock.deadline(None), None);
+        assert_eq!(left(clock.deadline(LIMIT)), 60);
+        // Waiting between operations, e.g. at the prompt, is not charged
+        std::thread::sleep(Duration::from_millis(600));
+        assert_eq!(left(clock.deadline(LIMIT)), 60);
+    }
+
+    #[test]
+    fn earlier_operations_use_up_the_limit() {
+        let clock = RunClock::default();
+        clock.charge(Duration::from_secs(45));
+        assert_eq!(left(clock.deadline(LIMIT)), 15);
+        clock.charge(Duration::from_secs(30));
+        assert_eq!(left(clock.deadline(LIMIT)), 0);
+        let limits = RunLimits {
+            time_limit: LIMIT,
+            ..RunLimits::default()
+        };
+        let budget = Budget::new(limits, clock.deadline(LIMIT));
+        assert_eq!(budget.expired(), Some(Limit::TimeLimit(LIMIT.unwrap())));
+    }
+
+    fn limits(max_count: Option<u64>, max_bytes: Option<u64>) -> RunLimits {
+        RunLimits {
+            max_count,
+            max_bytes,
+            time_limit: None,
+        }
+    }
+
+    #[test]
+    fn items_over_max_bytes_are_skipped_while_smaller_ones_fit() {
+        let mut budget = Budget::new(limits(None, Some(100)), None);
+        assert_eq!(budget.admit(60), Ok(true));
+        assert_eq!(budget.admit(50), Ok(false));
+        assert_eq!(budget.admit(40), Ok(true));
+        assert_eq!(budget.skipped(), (1, 50));
+        // Used up exactly: the next item that does not fit ends the run...
+        assert_eq!(budget.admit(1), Err(Limit::MaxBytes(100)));
+        // ...though empty ones (directories) never count against it
+        assert_eq!(budget.admit(0), Ok(true));
+
+        let report = budget.skipped_report().unwrap();
+        assert_eq!(report.limit, Limit::MaxBytes(100));
+        assert_eq!((report.skipped, report.skipped_bytes), (1, 50));
+        assert!(report.scan_complete);
+    }
+
+    #[test]
+    fn max_count_stops_after_n_items() {
+        let mut budget = Budget::new(limits(Some(2), Some(100)), None);
+        assert_eq!(budget.admit(10), Ok(true));
+        assert_eq!(budget.admit(200), Ok(false));
+        assert_eq!(budget.admit(10), Ok(true));
+        assert_eq!(budget.admit(10), Err(Limit::MaxCount(2)));
+        assert!(
+            Budget::new(limits(Some(2), None), None)
+                .skipped_report()
+                .is_none()
+        );
+    }
+}
@@ -1,5 +1,6 @@
+use crate::error::DeleterError;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Log entry for deleted items
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -63,3 +64,17 @@ pub fn generate_log_filename() -> PathBuf {
         }
     }
 }
+
+/// Read an NDJSON delete log written by a previous run
+pub async fn read_log(path: &Path) -> Result<Vec<DeletedItem>, DeleterError> {
+    let content = tokio::fs::read_to_string(path).await?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| DeleterError::Log(format!("{} line {}: {}", path.display(), i + 1, e)))
+        })
+        .collect()
+}
@@ -0,0 +1,166 @@
+use infer::Infer;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Bytes read from the start of a file to detect its content type
+const SNIFF_LEN: u64 = 8192;
+
+/// Reported for zero-length files, as by `file --mime-type`
+pub const EMPTY: &str = "inode/x-empty";
+
+/// Reported for text without a more specific signature
+pub const TEXT: &str = "text/plain";
+
+/// Reported when nothing is recognised
+pub const UNKNOWN: &str = "application/octet-stream";
+
+/// A content type pattern for `--mime`: `type/subtype`, `type/*` or `*/*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimePattern {
+    /// Lowercase top-level type, `None` for `*`
+    kind: Option<String>,
+    /// Lowercase subtype, `None` for `*`
+    subtype: Option<String>,
+}
+
+impl MimePattern {
+    pub fn matches(&self, mime: &str) -> bool {
+        let (kind, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+        self.kind
+            .as_deref()
+            .is_none_or(|k| k.eq_ignore_ascii_case(kind))
+            && self
+                .subtype
+                .as_deref()
+                .is_none_or(|s| s.eq_ignore_ascii_case(subtype))
+    }
+}
+
+impl FromStr for MimePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || {
+            format!(
+                "invalid content type '{}' (expected type/subtype, e.g. application/x-core or image/*)",
+                s
+            )
+        };
+        let (kind, subtype) = s.split_once('/').ok_or_else(invalid)?;
+        let part = |p: &str| -> Result<Option<String>, String> {
+            if p == "*" {
+                Ok(None)
+            } else if !p.is_empty()
+                && p.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+            {
+                Ok(Some(p.to_ascii_lowercase()))
+            } else {
+                Err(invalid())
+            }
+        };
+        let kind = part(kind)?;
+        let subtype = part(subtype)?;
+        if kind.is_none() && subtype.is_some() {
+            return Err(invalid());
+        }
+        Ok(Self { kind, subtype })
+    }
+}
+
+impl fmt::Display for MimePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.kind.as_deref().unwrap_or("*"),
+            self.subtype.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+/// Detects content types from magic bytes
+pub struct Sniffer {
+    infer: Infer,
+}
+
+impl Sniffer {
+    pub fn new() -> Self {
+        let mut infer = Infer::new();
+        // Checked before the generic ELF signature
+        infer.add("application/x-core", "core", is_core_dump);
+        Self { infer }
+    }
+
+    /// Content type of the file at `path`, from its first bytes
+    pub fn detect(&self, path: &Path) -> io::Result<&'static str> {
+        let mut buf = Vec::new();
+        open(path)?.take(SNIFF_LEN).read_to_end(&mut buf)?;
+        Ok(self.classify(&buf))
+    }
+
+    /// Content type of a file starting with `buf`
+    pub fn classify(&self, buf: &[u8]) -> &'static str {
+        if buf.is_empty() {
+            return EMPTY;
+        }
+        match self.infer.get(buf) {
+            Some(kind) => kind.mime_type(),
+            None if looks_like_text(buf) => TEXT,
+            None => UNKNOWN,
+        }
+    }
+}
+
+impl Default for Sniffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Open for reading without updating the access time where the platform allows it,
+/// so sniffing does not make files look recently used to `--time-field atime`
+fn open(path: &Path) -> io::Result<File> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        // O_NOATIME is refused (EPERM) on files owned by other users
+        if let Ok(file) = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOATIME)
+            .open(path)
+        {
+            return Ok(file);
+        }
+    }
+    File::open(path)
+}
+
+/// ELF file of type `ET_CORE`
+fn is_core_dump(buf: &[u8]) -> bool {
+    const ET_CORE: u16 = 4;
+    if buf.len() < 18 || !buf.starts_with(b"\x7fELF") {
+        return false;
+    }
+    let e_type = [buf[16], buf[17]];
+    match buf[5] {
+        1 => u16::from_le_bytes(e_type) == ET_CORE,
+        2 => u16::from_be_bytes(e_type) == ET_CORE,
+        _ => false,
+    }
+}
+
+/// UTF-8 without NUL bytes; a sequence cut off by the read limit still counts
+fn looks_like_text(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
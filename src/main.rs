@@ -1,89 +1,166 @@
-use clap::Parser;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::{
-    io::Write,
-    sync::atomic::{AtomicBool, Ordering},
+use spacefree::{
+    Defaults, DeleteConfig, DeleterError, DeletionReport, DriftPolicy, EntryKind, KeptEntry,
+    LogMode, PRESETS, Plan, Settings, Spacefree, StorageKind,
+    cli::{
+        ApplyArgs, Cli, Command, ConfigAction, DedupeArgs, DeleteArgs, DoctorArgs, PlanArgs,
+        PresetsAction, RestoreArgs, SelectArgs, flag_or_default, format_dirs, format_size,
+        is_root_path,
+    },
+    collect_paths,
+    restore::{restore_from_log, trash_item_count},
 };
+use std::{collections::HashMap, io::Write, path::PathBuf};
 use tokio::{fs, signal};
 use tracing::{debug, error, info, warn};
-use walkdir::WalkDir;
-
-// Module declarations
-mod cli;
-mod config;
-mod delete;
-mod error;
-mod log;
-mod scan;
-mod storage;
-
-// Re-exports for convenience
-use cli::{Cli, build_globset, format_dirs, format_size, is_root_path};
-use config::DeleteConfig;
-use delete::run_deletion_pipeline;
-use error::DeleterError;
-use log::LogMode;
-use scan::collect_paths;
-use storage::StorageKind;
-
-/// Global shutdown flag for graceful cancellation
-static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
-
-/// Check if shutdown has been requested
-pub fn is_shutdown_requested() -> bool {
-    SHUTDOWN_REQUESTED.load(Ordering::Relaxed)
-}
 
-/// Main application logic
-async fn run(cli: Cli) -> Result<(), DeleterError> {
-    let all_paths = collect_paths(&cli.paths, &cli.path_list_file).await?;
-    let (globset, exclude_glob) = build_globset(cli.glob.as_deref(), &cli.exclude)?;
+/// Delete matching files (default command)
+async fn delete(args: DeleteArgs, verbose: bool) -> Result<(), DeleterError> {
+    let select = &args.select;
+    let all_paths = collect_paths(&select.paths, &select.path_list_file).await?;
 
     println!("🔍 Scanning...");
 
-    let glob_pattern = cli.glob.as_deref().unwrap_or("**/*").to_string();
-
-    // Detect storage type and set optimal parallelism
-    let (parallelism, storage_kind) = if cli.parallelism == 0 {
-        // Auto-detect based on storage type
-        let kind = all_paths
-            .first()
-            .map(|p| StorageKind::from_path(p))
-            .unwrap_or(StorageKind::Unknown);
-        let optimal = kind.optimal_parallelism();
-        println!("  Storage: {:?} → parallelism: {}", kind, optimal);
-        (optimal, kind)
+    let log_mode = LogMode::from_opt(&args.log);
+    let pb = ProgressBar::new(0);
+
+    let mut builder = select
+        .to_builder(&all_paths)?
+        .trash(args.trash)
+        .dry_run(args.dry_run)
+        .parallelism(args.parallelism.unwrap_or(0))
+        .verbose(verbose)
+        .progress(pb.clone());
+    builder = args.limits.apply(builder);
+    if !args.dry_run {
+        if let Some(path) = log_mode.path() {
+            builder = builder.log_path(path);
+        }
+    }
+    let spacefree = builder.build()?;
+    let config = spacefree.config();
+    let glob_pattern = config.glob_pattern.clone();
+
+    if args.parallelism.unwrap_or(0) == 0 {
+        println!(
+            "  Storage: {:?} → parallelism: {}",
+            config.storage_kind, config.parallelism
+        );
+    }
+
+    check_root_guard(&all_paths, args.delete_root_dir, args.yes)?;
+
+    if let Some(target) = &config.free_target {
+        let (available, goal) = target.status()?;
+        println!(
+            "  Free space on {}: {} available, goal {} ({} first)",
+            target.path.display(),
+            format_size(available),
+            format_size(goal),
+            target.order
+        );
+        if available >= goal {
+            println!("Goal already met, nothing to delete.");
+            return Ok(());
+        }
+    }
+
+    // Full pre-scan with every filter applied, so the prompt shows exactly what will go
+    let preview = spacefree.preview().await;
+    if let Some(limit) = preview.stopped {
+        println!(
+            "⏹️  Stopped by {} while scanning, nothing deleted: {} matched item(s) ({}) so far",
+            limit,
+            preview.items(),
+            format_size(preview.bytes)
+        );
+        return Ok(());
+    }
+    if preview.is_empty() {
+        println!("Nothing matched.");
+        return Ok(());
+    }
+
+    let mode = if args.trash { "TRASH" } else { "PERMANENT" };
+    let item_type = if config.dirs {
+        "files/empty dirs"
     } else {
-        // User-specified parallelism, still detect storage for sorting optimization
-        let kind = all_paths
-            .first()
-            .map(|p| StorageKind::from_path(p))
-            .unwrap_or(StorageKind::Unknown);
-        (cli.parallelism, kind)
+        "files"
     };
 
-    let config = std::sync::Arc::new(DeleteConfig {
-        use_trash: cli.trash,
-        dry_run: cli.dry_run,
-        parallelism,
-        min_size: cli.min_size,
-        max_size: cli.max_size,
-        min_age: cli.min_age,
-        max_age: cli.max_age,
-        verbose: cli.verbose,
-        dirs: cli.dirs,
-        follow_symlinks: cli.follow_symlinks,
-        glob_pattern: glob_pattern.clone(),
-        glob_matcher: globset,
-        exclude_matcher: exclude_glob,
-        skip_glob_match: glob_pattern == "**/*",
-        storage_kind,
-    });
+    println!(
+        "Will scan and delete '{}' {} in {} with {} mode",
+        glob_pattern,
+        item_type,
+        format_dirs(&all_paths),
+        mode
+    );
+
+    if config.dirs || preview.dirs > 0 {
+        println!(
+            "Matched {} file(s) ({}) and {} director(ies)",
+            preview.files,
+            format_size(preview.bytes),
+            preview.dirs
+        );
+    } else {
+        println!(
+            "Matched {} file(s) ({})",
+            preview.files,
+            format_size(preview.bytes)
+        );
+    }
+    print_kept(&preview.kept, args.dry_run || verbose);
+    print_limits(config);
+
+    if !args.dry_run && !args.yes {
+        confirm()?;
+    }
+
+    println!("🗑️  Processing...");
+
+    start_progress(&pb, preview.items());
+
+    let report = spacefree.run().await?;
+
+    print_report(&report);
+    Ok(())
+}
+
+/// Size the progress bar and switch it to the bar style
+fn start_progress(pb: &ProgressBar, len: u64) {
+    pb.set_length(len);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+}
 
-    // Check for root directory and require explicit confirmation
-    for path in &all_paths {
+/// Ask for exactly `YES` before deleting; anything else cancels
+fn confirm() -> Result<(), DeleterError> {
+    print!("\nType exactly YES to continue: ");
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    if input.trim() != "YES" {
+        return Err(DeleterError::Cancelled);
+    }
+    Ok(())
+}
+
+/// Refuse root directory paths unless both `--delete-root-dir` and `-y` were given
+fn check_root_guard(
+    paths: &[PathBuf],
+    delete_root_dir: bool,
+    yes: bool,
+) -> Result<(), DeleterError> {
+    for path in paths {
         if is_root_path(path) {
-            if !cli.delete_root_dir {
+            if !delete_root_dir {
                 eprintln!("❌ ERROR: Attempting to delete root directory");
                 eprintln!("This is extremely dangerous and could destroy your entire system.");
                 eprintln!();
@@ -94,7 +171,7 @@ async fn run(cli: Cli) -> Result<(), DeleterError> {
                 eprintln!("Example: spf / -y --delete-root-dir");
                 return Err(DeleterError::Cancelled);
             }
-            if !cli.yes {
+            if !yes {
                 eprintln!("❌ ERROR: Deleting root directory requires -y flag");
                 eprintln!();
                 eprintln!("You must use both:");
@@ -104,130 +181,653 @@ async fn run(cli: Cli) -> Result<(), DeleterError> {
             }
         }
     }
+    Ok(())
+}
+
+/// Show what retention holds back: each entry and why when `list` is set, else the count
+fn print_kept(kept: &[KeptEntry], list: bool) {
+    if kept.is_empty() {
+        return;
+    }
+    if !list {
+        println!(
+            "Keeping {} match(es) by retention (list them with --dry-run)",
+            kept.len()
+        );
+        return;
+    }
+    println!("Keeping {} match(es) by retention:", kept.len());
+    for entry in kept {
+        println!("  {}", entry);
+    }
+}
+
+/// Show the run limits, if any, before confirming
+fn print_limits(config: &DeleteConfig) {
+    if !config.limits.is_empty() {
+        println!("  Run limits: {}", config.limits);
+    }
+}
 
-    // Separate directories and individual files
-    let mut directories = Vec::new();
-    let mut individual_files = Vec::new();
+/// Print per-root counts, failures and the final summary of a deletion run
+fn print_report(report: &DeletionReport) {
+    if report.roots.len() > 1 {
+        for root in &report.roots {
+            println!(
+                "  {}: {} removed, {} failed, {} skipped, {}",
+                root.root.display(),
+                root.deleted,
+                root.failed,
+                root.skipped,
+                format_size(root.bytes_freed)
+            );
+        }
+    }
 
-    for path in &all_paths {
-        match fs::metadata(path).await {
-            Ok(m) if m.is_dir() => directories.push(path.clone()),
-            Ok(m) if m.is_file() => individual_files.push(path.clone()),
-            Ok(_) => warn!(
-                "Path is neither file nor directory, skipping: {}",
-                path.display()
-            ),
-            Err(e) => warn!("Cannot access path ({}), skipping: {}", e, path.display()),
+    if !report.drifted.is_empty() {
+        println!(
+            "  Skipped {} item(s) changed since they were selected:",
+            report.drifted.len()
+        );
+        for item in &report.drifted {
+            println!("  - {} ({})", item.path.display(), item.drift);
         }
     }
 
-    // Quick scan for preview (sample first few paths)
-    let mut preview_files = 0;
-    let mut _preview_bytes = 0;
-    let mut preview_dirs = 0;
+    if let Some(space) = &report.free_space {
+        let verb = if report.dry_run { "would be" } else { "now" };
+        if space.achieved {
+            println!(
+                "🎯 Free space goal of {} reached: {} {} available (was {})",
+                format_size(space.goal),
+                verb,
+                format_size(space.available_after),
+                format_size(space.available_before)
+            );
+        } else {
+            println!(
+                "⚠️  Free space goal of {} not reached: {} {} available (was {})",
+                format_size(space.goal),
+                verb,
+                format_size(space.available_after),
+                format_size(space.available_before)
+            );
+        }
+    }
 
-    for path in &individual_files {
-        if let Ok(m) = fs::metadata(path).await {
-            preview_files += 1;
-            _preview_bytes += m.len();
+    if let Some(stop) = &report.stopped {
+        let would = if report.dry_run { "would be " } else { "" };
+        let mut parts = Vec::new();
+        if stop.skipped > 0 {
+            parts.push(format!(
+                "{} item(s) ({}) {}skipped as too large",
+                stop.skipped,
+                format_size(stop.skipped_bytes),
+                would
+            ));
+        }
+        // Without a complete scan, only what was already found is known
+        let (found, more) = if stop.scan_complete {
+            ("matched", "")
+        } else {
+            ("queued", "; the scan was cut short, so more may match")
+        };
+        if stop.queued > 0 || parts.is_empty() {
+            parts.push(format!(
+                "{} {} item(s) ({}) {}left",
+                stop.queued,
+                found,
+                format_size(stop.queued_bytes),
+                would
+            ));
         }
+        println!(
+            "⏹️  Stopped by {}: {}{}",
+            stop.limit,
+            parts.join(", "),
+            more
+        );
     }
-    for dir in &directories {
-        let walkdir = WalkDir::new(dir).follow_links(cli.follow_symlinks);
-        for entry in walkdir.into_iter().filter_map(|e| e.ok()).take(1000) {
-            if entry.file_type().is_file() {
-                if let Ok(m) = entry.metadata() {
-                    if m.len() >= cli.min_size {
-                        preview_files += 1;
-                        _preview_bytes += m.len();
-                    }
-                }
-            } else if cli.dirs && entry.file_type().is_dir() {
-                preview_dirs += 1;
+
+    if report.dry_run {
+        println!("Preview complete.");
+    } else {
+        if report.failed > 0 {
+            eprintln!();
+            eprintln!("⚠️  {} item(s) failed to delete:", report.failed);
+            for failure in &report.failures {
+                eprintln!(
+                    "  - {} ({:?}: {})",
+                    failure.path.display(),
+                    failure.kind,
+                    failure.message
+                );
             }
         }
+        let non_empty = report.skipped - report.drifted.len() as u64;
+        if non_empty > 0 {
+            println!("  Skipped {} non-empty director(ies)", non_empty);
+        }
+        println!(
+            "✅ Removed {} item(s), freed {} in {:.2?}",
+            report.deleted,
+            format_size(report.bytes_freed),
+            report.elapsed
+        );
     }
+}
 
-    let total_estimate = preview_files + preview_dirs;
-    if total_estimate == 0 {
-        println!("Nothing matched.");
+/// Write a plan of everything a delete run would remove
+async fn plan(args: PlanArgs) -> Result<(), DeleterError> {
+    let select = &args.select;
+    let all_paths = collect_paths(&select.paths, &select.path_list_file).await?;
+
+    println!("🔍 Scanning...");
+    let spacefree = select.to_builder(&all_paths)?.build()?;
+    let plan = spacefree.plan().await;
+    plan.write(&args.output).await?;
+
+    let summary = plan.summary();
+    println!(
+        "📝 Planned {} file(s) ({}) and {} director(ies) in {}",
+        summary.files,
+        format_size(summary.bytes),
+        summary.dirs,
+        format_dirs(&plan.roots)
+    );
+    println!("Plan written to {}", args.output.display());
+    Ok(())
+}
+
+/// Delete exactly the entries of a reviewed plan
+async fn apply(args: ApplyArgs, verbose: bool) -> Result<(), DeleterError> {
+    let plan = Plan::read(&args.plan).await?;
+    if plan.entries.is_empty() {
+        println!("Nothing to apply.");
         return Ok(());
     }
 
-    let mode = if cli.trash { "TRASH" } else { "PERMANENT" };
-    let item_type = if cli.dirs {
-        "files/empty dirs"
-    } else {
-        "files"
+    check_root_guard(&plan.roots, args.delete_root_dir, args.yes)?;
+
+    let pb = ProgressBar::new(0);
+    let mut builder = Spacefree::builder()
+        .paths(plan.roots.iter().cloned())
+        .allow_protected(args.allow_protected)
+        .trash(args.trash)
+        .dry_run(args.dry_run)
+        .parallelism(args.parallelism.unwrap_or(0))
+        .verbose(verbose)
+        .progress(pb.clone());
+    for path in &args.protect {
+        builder = builder.protect(path);
+    }
+    builder = args.limits.apply(builder);
+    if !args.dry_run {
+        if let Some(path) = LogMode::from_opt(&args.log).path() {
+            builder = builder.log_path(path);
+        }
+    }
+    let spacefree = builder.build()?;
+    spacefree.check_plan(&plan)?;
+
+    // Check every entry up front so drift is visible before confirming
+    let drifted = plan.verify().await;
+    if !drifted.is_empty() {
+        eprintln!(
+            "⚠️  {} item(s) changed since the plan was created:",
+            drifted.len()
+        );
+        for item in &drifted {
+            eprintln!("  - {} ({})", item.path.display(), item.drift);
+        }
+        if args.on_drift == DriftPolicy::Abort {
+            eprintln!("Re-run `spa plan`, or use --on-drift skip to delete the unchanged entries.");
+            return Err(DeleterError::PlanDrift(drifted.len()));
+        }
+    }
+
+    let summary = plan.summary();
+    let mode = if args.trash { "TRASH" } else { "PERMANENT" };
+    println!(
+        "Will delete {} file(s) ({}) and {} director(ies) planned for '{}' in {} with {} mode",
+        summary.files,
+        format_size(summary.bytes),
+        summary.dirs,
+        plan.selection,
+        format_dirs(&plan.roots),
+        mode
+    );
+    print_limits(spacefree.config());
+
+    if !args.dry_run && !args.yes {
+        confirm()?;
+    }
+
+    println!("🗑️  Processing...");
+
+    start_progress(&pb, summary.items());
+    // Drift was already reported above; entries that change from here on are skipped
+    let report = spacefree.apply(&plan, DriftPolicy::Skip).await?;
+    print_report(&report);
+
+    Ok(())
+}
+
+/// Delete all but one copy of each set of identical files
+async fn dedupe(args: DedupeArgs, verbose: bool) -> Result<(), DeleterError> {
+    let delete = &args.delete;
+    let select = &delete.select;
+    let all_paths = collect_paths(&select.paths, &select.path_list_file).await?;
+    let policy = args.keep_policy()?;
+
+    println!("🔍 Scanning...");
+
+    let pb = ProgressBar::new(0);
+    let mut builder = select
+        .to_builder(&all_paths)?
+        .trash(delete.trash)
+        .dry_run(delete.dry_run)
+        .parallelism(delete.parallelism.unwrap_or(0))
+        .verbose(verbose)
+        .progress(pb.clone());
+    builder = delete.limits.apply(builder);
+    if !delete.dry_run {
+        if let Some(path) = LogMode::from_opt(&delete.log).path() {
+            builder = builder.log_path(path);
+        }
+    }
+    let spacefree = builder.build()?;
+
+    check_root_guard(&all_paths, delete.delete_root_dir, delete.yes)?;
+
+    println!("🔗 Comparing contents...");
+    let groups = match spacefree.duplicates(&policy).await {
+        Ok(groups) => groups,
+        Err(limit) => {
+            println!(
+                "⏹️  Stopped by {} while comparing contents, nothing deleted",
+                limit
+            );
+            return Ok(());
+        }
     };
+    if groups.is_empty() {
+        println!("No duplicates found.");
+        return Ok(());
+    }
+
+    let mut removals = 0u64;
+    let mut redundant = 0u64;
+    for group in &groups {
+        println!("{} × {}:", format_size(group.size), group.remove.len() + 1);
+        println!("  keep    {}", group.keep.path.display());
+        for entry in &group.remove {
+            println!("  delete  {}", entry.path.display());
+        }
+        removals += group.remove.len() as u64;
+        redundant += group.redundant_bytes();
+    }
 
+    let mode = if delete.trash { "TRASH" } else { "PERMANENT" };
     println!(
-        "Will scan and delete '{}' {} in {} with {} mode",
-        glob_pattern,
-        item_type,
+        "Will delete {} duplicate(s) ({}) in {} group(s) under {} with {} mode",
+        removals,
+        format_size(redundant),
+        groups.len(),
         format_dirs(&all_paths),
         mode
     );
+    print_limits(spacefree.config());
+
+    if !delete.dry_run && !delete.yes {
+        confirm()?;
+    }
 
-    println!("Estimated items: {}", total_estimate);
+    println!("🗑️  Processing...");
 
-    if !cli.dry_run && !cli.yes {
-        print!("\nType exactly YES to continue: ");
-        std::io::stdout().flush()?;
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        let trimmed = input.trim();
-        if trimmed != "YES" {
-            return Err(DeleterError::Cancelled);
+    start_progress(&pb, removals);
+    let report = spacefree.dedupe(groups).await?;
+    print_report(&report);
+
+    Ok(())
+}
+
+/// Restore items trashed by a previous run
+async fn restore(args: RestoreArgs) -> Result<(), DeleterError> {
+    println!("♻️  Restoring from {}...", args.log.display());
+
+    let report = restore_from_log(&args.log, args.on_conflict, args.dry_run).await?;
+
+    for path in &report.missing {
+        println!("  not in trash: {}", path.display());
+    }
+    for path in &report.conflicts {
+        println!("  exists, skipped: {}", path.display());
+    }
+    for path in &report.renamed {
+        println!("  restored as: {}", path.display());
+    }
+    if !report.failures.is_empty() {
+        eprintln!();
+        eprintln!("⚠️  {} item(s) failed to restore:", report.failures.len());
+        for (path, reason) in &report.failures {
+            eprintln!("  - {} ({})", path.display(), reason);
         }
     }
 
-    println!("🗑️  Processing...");
+    let verb = if args.dry_run {
+        "Would restore"
+    } else {
+        "Restored"
+    };
+    println!(
+        "✅ {} {} item(s) ({} renamed, {} overwritten), {} conflict(s) skipped, {} missing",
+        verb,
+        report.restored + report.renamed.len() as u64 + report.overwritten,
+        report.renamed.len(),
+        report.overwritten,
+        report.conflicts.len(),
+        report.missing.len()
+    );
 
-    let log_mode = LogMode::from_opt(&cli.log);
-    let log_path = if cli.dry_run { None } else { log_mode.path() };
+    Ok(())
+}
 
-    // Create progress bar
-    let pb = ProgressBar::new(total_estimate as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-            )
-            .unwrap()
-            .progress_chars("#>-"),
+/// List matching files without deleting anything
+async fn scan(select: SelectArgs) -> Result<(), DeleterError> {
+    let all_paths = collect_paths(&select.paths, &select.path_list_file).await?;
+    let spacefree = select.to_builder(&all_paths)?.dry_run(true).build()?;
+
+    let mut files = 0u64;
+    let mut dirs = 0u64;
+    let mut bytes = 0u64;
+    let mut results = spacefree.scan();
+    while let Some(result) = results.recv().await {
+        if result.is_dir() {
+            dirs += 1;
+            println!("{:>10}  {}/", "-", result.path.display());
+        } else {
+            files += 1;
+            bytes += result.size;
+            let size = if result.kind == EntryKind::File {
+                format_size(result.size)
+            } else {
+                "-".to_string()
+            };
+            match result.owner {
+                Some(owner) => println!(
+                    "{:>10}  {}  {}{}",
+                    size,
+                    owner,
+                    result.path.display(),
+                    result.kind.suffix()
+                ),
+                None => println!(
+                    "{:>10}  {}{}",
+                    size,
+                    result.path.display(),
+                    result.kind.suffix()
+                ),
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{} file(s), {} director(ies), {} total",
+        files,
+        dirs,
+        format_size(bytes)
     );
+    Ok(())
+}
 
-    let (deleted, failed, bytes, failed_paths) =
-        run_deletion_pipeline(directories, individual_files, config, pb, log_path).await?;
+/// Summarize matching files by count, size and extension
+async fn stats(select: SelectArgs) -> Result<(), DeleterError> {
+    const TOP: usize = 10;
 
-    if cli.dry_run {
-        println!("Preview complete.");
+    let all_paths = collect_paths(&select.paths, &select.path_list_file).await?;
+    let spacefree = select.to_builder(&all_paths)?.dry_run(true).build()?;
+
+    println!("🔍 Scanning...");
+
+    let mut files = 0u64;
+    let mut dirs = 0u64;
+    let mut bytes = 0u64;
+    let mut by_extension: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut largest: Vec<(u64, PathBuf)> = Vec::new();
+
+    let mut results = spacefree.scan();
+    while let Some(result) = results.recv().await {
+        if result.is_dir() {
+            dirs += 1;
+            continue;
+        }
+        files += 1;
+        bytes += result.size;
+
+        let extension = result
+            .path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "(none)".to_string());
+        let entry = by_extension.entry(extension).or_default();
+        entry.0 += 1;
+        entry.1 += result.size;
+
+        largest.push((result.size, result.path));
+        if largest.len() > TOP * 4 {
+            largest.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+            largest.truncate(TOP);
+        }
+    }
+    largest.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+    largest.truncate(TOP);
+
+    println!(
+        "Matched {} file(s), {} director(ies), {} in {}",
+        files,
+        dirs,
+        format_size(bytes),
+        format_dirs(&all_paths)
+    );
+
+    if !by_extension.is_empty() {
+        let mut extensions: Vec<_> = by_extension.into_iter().collect();
+        extensions.sort_by_key(|(_, (_, size))| std::cmp::Reverse(*size));
+        println!();
+        println!("By extension:");
+        for (extension, (count, size)) in extensions.iter().take(TOP) {
+            println!(
+                "  {:>10}  {:>8} file(s)  {}",
+                format_size(*size),
+                count,
+                extension
+            );
+        }
+    }
+
+    if !largest.is_empty() {
+        println!();
+        println!("Largest files:");
+        for (size, path) in &largest {
+            println!("  {:>10}  {}", format_size(*size), path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Check platform support, storage detection and trash availability
+async fn doctor(args: DoctorArgs) -> Result<(), DeleterError> {
+    println!("🩺 spacefree {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "  Platform: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    println!("  CPUs: {}", num_cpus::get());
+
+    let paths = if args.paths.is_empty() {
+        vec![PathBuf::from(".")]
     } else {
-        if failed > 0 {
-            eprintln!();
-            eprintln!("⚠️  {} item(s) failed to delete:", failed);
-            for path in &failed_paths {
-                eprintln!("  - {}", path.display());
-            }
-            eprintln!("  (Check file permissions)");
+        args.paths
+    };
+    println!();
+    println!("Storage:");
+    for path in &paths {
+        if fs::metadata(path).await.is_err() {
+            println!("  ⚠️  {}: not accessible", path.display());
+            continue;
         }
+        let kind = StorageKind::from_path(path);
+        let note = if kind == StorageKind::Unknown {
+            " (detection failed, using conservative defaults)"
+        } else {
+            ""
+        };
         println!(
-            "✅ Removed {} item(s), freed {}",
-            deleted,
-            format_size(bytes)
+            "  ✅ {}: {:?} → parallelism: {}{}",
+            path.display(),
+            kind,
+            kind.optimal_parallelism(),
+            note
         );
     }
 
+    println!();
+    println!("Trash:");
+    match trash_item_count().await {
+        Ok(count) => println!("  ✅ Trash readable ({} item(s)), restore supported", count),
+        Err(e) => println!("  ⚠️  {}", e),
+    }
+
+    println!();
+    println!("Logging:");
+    let probe = PathBuf::from(".spacefree-doctor");
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            println!("  ✅ Current directory is writable for auto-named logs");
+        }
+        Err(e) => println!("  ⚠️  Cannot create auto-named logs here: {}", e),
+    }
+
     Ok(())
 }
 
+/// Print the effective configuration
+fn config_show(settings: &Settings, profile: Option<&str>, defaults: &Defaults) {
+    match &settings.source {
+        Some(path) => println!("# Config file: {}", path.display()),
+        None => match Settings::default_path() {
+            Some(path) => println!("# Config file: {} (not found)", path.display()),
+            None => println!("# Config file: none"),
+        },
+    }
+    if let Some(name) = profile {
+        println!("# Profile: {}", name);
+    }
+    if !settings.profiles.is_empty() {
+        let names: Vec<&str> = settings.profiles.keys().map(String::as_str).collect();
+        println!("# Available profiles: {}", names.join(", "));
+    }
+    match toml::to_string(defaults) {
+        Ok(text) if text.trim().is_empty() => println!("# (no defaults set)"),
+        Ok(text) => print!("{}", text),
+        Err(e) => eprintln!("⚠️  Cannot render configuration: {}", e),
+    }
+}
+
+/// Show what each built-in preset targets
+fn presets_list() {
+    for preset in PRESETS {
+        println!("{} - {}", preset.name, preset.description);
+        for rule in preset.dirs {
+            if rule.markers.is_empty() {
+                println!("  dir      {}/", rule.name);
+            } else {
+                println!(
+                    "  dir      {}/ (next to {})",
+                    rule.name,
+                    rule.markers.join(" or ")
+                );
+            }
+        }
+        for glob in preset.include {
+            println!("  include  {}", glob);
+        }
+        for glob in preset.exclude {
+            println!("  exclude  {}", glob);
+        }
+    }
+}
+
+/// Main application logic
+async fn run(
+    cli: Cli,
+    settings: Settings,
+    defaults: Defaults,
+    verbose: bool,
+) -> Result<(), DeleterError> {
+    let profile = cli.profile.clone();
+    match cli.into_command() {
+        Command::Delete(mut args) => {
+            args.apply_defaults(&defaults)?;
+            delete(args, verbose).await
+        }
+        Command::Scan(mut select) => {
+            select.apply_defaults(&defaults)?;
+            scan(select).await
+        }
+        Command::Plan(mut args) => {
+            args.select.apply_defaults(&defaults)?;
+            plan(args).await
+        }
+        Command::Apply(mut args) => {
+            args.apply_defaults(&defaults)?;
+            apply(args, verbose).await
+        }
+        Command::Dedupe(mut args) => {
+            args.apply_defaults(&defaults)?;
+            dedupe(args, verbose).await
+        }
+        Command::Restore(args) => restore(args).await,
+        Command::Stats(mut select) => {
+            select.apply_defaults(&defaults)?;
+            stats(select).await
+        }
+        Command::Doctor(args) => doctor(args).await,
+        Command::Config(args) => match args.action {
+            ConfigAction::Show => {
+                config_show(&settings, profile.as_deref(), &defaults);
+                Ok(())
+            }
+        },
+        Command::Presets(args) => match args.action {
+            PresetsAction::List => {
+                presets_list();
+                Ok(())
+            }
+        },
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), DeleterError> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_args();
+
+    // Config file defaults (merged with --profile); CLI flags take precedence
+    let settings = Settings::load(cli.config.as_deref())?;
+    let defaults = settings.resolve(cli.profile.as_deref())?;
+    let verbose = flag_or_default(cli.verbose, cli.no_verbose, defaults.verbose);
 
     // Set log level based on verbose flag
-    if cli.verbose {
+    if verbose {
         tracing_subscriber::fmt()
             .with_max_level(tracing::Level::INFO)
             .init();
@@ -244,12 +844,12 @@ async fn main() -> Result<(), DeleterError> {
             return;
         }
         println!("\n⚠️  Shutdown requested (Ctrl+C), finishing current operations...");
-        SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
+        spacefree::request_shutdown();
     });
 
     debug!("Starting spacefree with CLI args: {:?}", cli);
 
-    if let Err(e) = run(cli).await {
+    if let Err(e) = run(cli, settings, defaults, verbose).await {
         error!("Application error: {}", e);
         return Err(e);
     }
@@ -0,0 +1,225 @@
+use chrono::{Local, LocalResult, NaiveDate, TimeZone};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// Named captures a `--name-date` regex may use; `year` or `epoch` is required
+const FIELDS: [&str; 7] = ["year", "month", "day", "hour", "minute", "second", "epoch"];
+
+/// A date embedded in file names, e.g. `%Y-%m-%dT%H%M` for `db-2026-03-14T0200.sql.gz`.
+///
+/// Written either strftime-style (`%Y %y %m %d %H %M %S %s %%`, other text literal)
+/// or as a regex with named captures (`(?P<year>\d{4})...`). The first match
+/// anywhere in the name is used and read as local time.
+#[derive(Debug, Clone)]
+pub struct NameDate {
+    format: String,
+    regex: Regex,
+}
+
+impl NameDate {
+    /// Date found in the file name of `path`, if any
+    pub fn time_of(&self, path: &Path) -> Option<SystemTime> {
+        let name = path.file_name()?.to_string_lossy();
+        self.parse(&name)
+    }
+
+    /// Date found in `name`, if any; impossible dates and non-numeric fields count as none
+    pub fn parse(&self, name: &str) -> Option<SystemTime> {
+        let caps = self.regex.captures(name)?;
+        if let Some(epoch) = caps.name("epoch") {
+            let secs = epoch.as_str().parse().ok()?;
+            return Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        }
+        let year = match caps.name("year") {
+            Some(m) if m.as_str().len() == 2 => {
+                // As strftime's %y: 69-99 are 1900s, 00-68 are 2000s
+                let yy: i32 = m.as_str().parse().ok()?;
+                if yy >= 69 { 1900 + yy } else { 2000 + yy }
+            }
+            Some(m) => m.as_str().parse().ok()?,
+            None => return None,
+        };
+        let date =
+            NaiveDate::from_ymd_opt(year, number(&caps, "month", 1)?, number(&caps, "day", 1)?)?;
+        let naive = date.and_hms_opt(
+            number(&caps, "hour", 0)?,
+            number(&caps, "minute", 0)?,
+            number(&caps, "second", 0)?,
+        )?;
+        match Local.from_local_datetime(&naive) {
+            LocalResult::Single(time) => Some(time.into()),
+            // Repeated hour when clocks go back: take the first occurrence (chrono
+            // orders the pair by offset, not by instant)
+            LocalResult::Ambiguous(a, b) => Some(a.min(b).into()),
+            LocalResult::None => None,
+        }
+    }
+}
+
+/// A numeric capture, `default` if it did not take part in the match, and
+/// `None` if it did but is not a number (e.g. `Mar` for a month)
+fn number(caps: &Captures, name: &str, default: u32) -> Option<u32> {
+    match caps.name(name) {
+        Some(m) => m.as_str().parse().ok(),
+        None => Some(default),
+    }
+}
+
+impl FromStr for NameDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern = if s.contains("(?P<") || s.contains("(?<") {
+            s.to_string()
+        } else {
+            strftime_to_regex(s)?
+        };
+        let regex =
+            Regex::new(&pattern).map_err(|e| format!("invalid name date '{}': {}", s, e))?;
+        let names: Vec<&str> = regex.capture_names().flatten().collect();
+        if !names.contains(&"year") && !names.contains(&"epoch") {
+            return Err(format!(
+                "name date '{}' has no year (use %Y, %y or %s, or a (?P<year>...) capture)",
+                s
+            ));
+        }
+        if let Some(name) = names.iter().find(|name| !FIELDS.contains(name)) {
+            return Err(format!(
+                "unknown capture '{}' in name date '{}' (expected {})",
+                name,
+                s,
+                FIELDS.join(", ")
+            ));
+        }
+        Ok(Self {
+            format: s.to_string(),
+            regex,
+        })
+    }
+}
+
+impl fmt::Display for NameDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format)
+    }
+}
+
+/// Translate a strftime-style format into a regex with named captures
+fn strftime_to_regex(format: &str) -> Result<String, String> {
+    let mut pattern = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+        let capture = match chars.next() {
+            Some('Y') => r"(?P<year>\d{4})",
+            Some('y') => r"(?P<year>\d{2})",
+            Some('m') => r"(?P<month>\d{2})",
+            Some('d') => r"(?P<day>\d{2})",
+            Some('H') => r"(?P<hour>\d{2})",
+            Some('M') => r"(?P<minute>\d{2})",
+            Some('S') => r"(?P<second>\d{2})",
+            Some('s') => r"(?P<epoch>\d{1,10})",
+            Some('%') => "%",
+            Some(other) => {
+                return Err(format!(
+                    "unsupported '%{}' in name date '{}' (supported: %Y %y %m %d %H %M %S %s %%)",
+                    other, format
+                ));
+            }
+            None => return Err(format!("name date '{}' ends with a lone '%'", format)),
+        };
+        pattern.push_str(capture);
+    }
+    Ok(pattern)
+}
+
+/// What age filters and retention do with names the date cannot be parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NameDateFallback {
+    /// Never select them by age, and always keep them under retention
+    #[default]
+    Skip,
+    /// Use the --time-field timestamp instead (mtime by default)
+    TimeField,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> SystemTime {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap().into()
+    }
+
+    #[test]
+    fn strftime_translates_to_named_captures() {
+        assert_eq!(
+            strftime_to_regex("%Y-%m-%d").unwrap(),
+            r"(?P<year>\d{4})\-(?P<month>\d{2})\-(?P<day>\d{2})"
+        );
+        assert_eq!(
+            strftime_to_regex("%s.log").unwrap(),
+            r"(?P<epoch>\d{1,10})\.log"
+        );
+        assert_eq!(strftime_to_regex("100%%").unwrap(), "100%");
+    }
+
+    #[test]
+    fn strftime_rejects_unsupported_and_lone_percent() {
+        assert!(
+            strftime_to_regex("%b")
+                .unwrap_err()
+                .contains("unsupported '%b'")
+        );
+        assert!(strftime_to_regex("%Y-%").unwrap_err().contains("lone '%'"));
+        assert!("%m-%d".parse::<NameDate>().unwrap_err().contains("no year"));
+    }
+
+    #[test]
+    fn parses_dates_from_names() {
+        crate::pin_local_time_zone();
+        let format: NameDate = "%Y-%m-%dT%H%M".parse().unwrap();
+        assert_eq!(
+            format.parse("db-2026-03-14T0200.sql.gz"),
+            Some(local(2026, 3, 14, 2, 0, 0))
+        );
+        let short: NameDate = "%y%m%d".parse().unwrap();
+        assert_eq!(short.parse("log-990102"), Some(local(1999, 1, 2, 0, 0, 0)));
+        assert_eq!(short.parse("log-260102"), Some(local(2026, 1, 2, 0, 0, 0)));
+        let epoch: NameDate = "%s".parse().unwrap();
+        assert_eq!(
+            epoch.parse("snap-1700000000"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(format.parse("notes.txt"), None);
+        assert_eq!(format.parse("db-2026-02-30T0200"), None);
+    }
+
+    #[test]
+    fn non_numeric_capture_is_no_date() {
+        crate::pin_local_time_zone();
+        let regex: NameDate = r"(?P<year>\d{4})-(?P<month>\w+)".parse().unwrap();
+        assert_eq!(regex.parse("2026-Mar"), None);
+        assert_eq!(regex.parse("2026-03"), Some(local(2026, 3, 1, 0, 0, 0)));
+    }
+
+    #[test]
+    fn skipped_local_time_is_no_date() {
+        crate::pin_local_time_zone();
+        let format: NameDate = "%Y%m%d-%H%M".parse().unwrap();
+        assert_eq!(format.parse("x-20260329-0230"), None);
+        assert_eq!(
+            format.parse("x-20261025-0230"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_888_200))
+        );
+    }
+}
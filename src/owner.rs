@@ -0,0 +1,192 @@
+use crate::error::DeleterError;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// How `--perm` compares permission bits (as in `find -perm`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermMatch {
+    /// Exactly these bits (`644`)
+    Exact(u32),
+    /// All of these bits set (`-002`)
+    All(u32),
+    /// Any of these bits set (`/022`)
+    Any(u32),
+}
+
+impl PermMatch {
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match *self {
+            PermMatch::Exact(bits) => mode == bits,
+            PermMatch::All(bits) => mode & bits == bits,
+            PermMatch::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+impl FromStr for PermMatch {
+    type Err = String;
+
+    /// Octal mode, optionally prefixed with `-` (all bits) or `/` (any bit)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (make, digits): (fn(u32) -> PermMatch, &str) = match s.as_bytes().first() {
+            Some(b'-') => (PermMatch::All, &s[1..]),
+            Some(b'/') => (PermMatch::Any, &s[1..]),
+            _ => (PermMatch::Exact, s),
+        };
+        // from_str_radix alone would also take a sign, as in `+644`
+        let octal = !digits.is_empty() && digits.bytes().all(|b| matches!(b, b'0'..=b'7'));
+        let bits = u32::from_str_radix(digits, 8)
+            .ok()
+            .filter(|bits| octal && *bits <= 0o7777)
+            .ok_or_else(|| format!("invalid octal mode: {}", s))?;
+        Ok(make(bits))
+    }
+}
+
+impl fmt::Display for PermMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermMatch::Exact(bits) => write!(f, "{:o}", bits),
+            PermMatch::All(bits) => write!(f, "-{:o}", bits),
+            PermMatch::Any(bits) => write!(f, "/{:o}", bits),
+        }
+    }
+}
+
+/// Owner, group and permission bits of a scanned entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ownership {
+    pub uid: u32,
+    pub gid: u32,
+    /// Permission bits (`mode & 0o7777`)
+    pub mode: u32,
+}
+
+impl Ownership {
+    #[cfg(unix)]
+    pub fn of(metadata: &std::fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(Self {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mode: metadata.mode() & 0o7777,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(_metadata: &std::fs::Metadata) -> Option<Self> {
+        None
+    }
+}
+
+impl fmt::Display for Ownership {
+    /// `user:group 0644`, falling back to numeric ids for unknown accounts
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} {:04o}",
+            account_name(false, self.uid),
+            account_name(true, self.gid),
+            self.mode
+        )
+    }
+}
+
+/// User or group name for an id, cached since listings repeat the same few owners
+fn account_name(group: bool, id: u32) -> String {
+    static NAMES: Mutex<Option<HashMap<(bool, u32), String>>> = Mutex::new(None);
+    let mut names = NAMES.lock().unwrap();
+    names
+        .get_or_insert_with(HashMap::new)
+        .entry((group, id))
+        .or_insert_with(|| lookup_name(group, id).unwrap_or_else(|| id.to_string()))
+        .clone()
+}
+
+#[cfg(unix)]
+fn lookup_name(group: bool, id: u32) -> Option<String> {
+    let name = if group {
+        uzers::get_group_by_gid(id)?.name().to_owned()
+    } else {
+        uzers::get_user_by_uid(id)?.name().to_owned()
+    };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn lookup_name(_group: bool, _id: u32) -> Option<String> {
+    None
+}
+
+/// Resolve a user name to its uid
+#[cfg(unix)]
+pub fn user_id(name: &str) -> Result<u32, DeleterError> {
+    uzers::get_user_by_name(name)
+        .map(|user| user.uid())
+        .ok_or_else(|| DeleterError::Config(format!("unknown user '{}'", name)))
+}
+
+/// Resolve a group name to its gid
+#[cfg(unix)]
+pub fn group_id(name: &str) -> Result<u32, DeleterError> {
+    uzers::get_group_by_name(name)
+        .map(|group| group.gid())
+        .ok_or_else(|| DeleterError::Config(format!("unknown group '{}'", name)))
+}
+
+#[cfg(not(unix))]
+pub fn user_id(_name: &str) -> Result<u32, DeleterError> {
+    Err(DeleterError::Config(
+        "owner filters are only supported on Unix".to_string(),
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn group_id(_name: &str) -> Result<u32, DeleterError> {
+    Err(DeleterError::Config(
+        "owner filters are only supported on Unix".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perm_parses_exact_all_and_any() {
+        assert_eq!("644".parse(), Ok(PermMatch::Exact(0o644)));
+        assert_eq!("0644".parse(), Ok(PermMatch::Exact(0o644)));
+        assert_eq!("-002".parse(), Ok(PermMatch::All(0o002)));
+        assert_eq!("/022".parse(), Ok(PermMatch::Any(0o022)));
+        assert_eq!(" 4755 ".parse(), Ok(PermMatch::Exact(0o4755)));
+    }
+
+    #[test]
+    fn perm_rejects_non_octal_and_out_of_range() {
+        for bad in ["", "-", "/", "8", "64a", "rw-r--r--", "17777", "+644"] {
+            assert!(bad.parse::<PermMatch>().is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn perm_display_round_trips() {
+        for text in ["644", "-2", "/22"] {
+            assert_eq!(text.parse::<PermMatch>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn perm_matches_mode_bits() {
+        assert!(PermMatch::Exact(0o644).matches(0o100644));
+        assert!(!PermMatch::Exact(0o644).matches(0o100664));
+        assert!(PermMatch::All(0o022).matches(0o777));
+        assert!(!PermMatch::All(0o022).matches(0o755));
+        assert!(PermMatch::Any(0o022).matches(0o664));
+        assert!(!PermMatch::Any(0o022).matches(0o644));
+        assert!(PermMatch::Any(0).matches(0o600));
+    }
+}
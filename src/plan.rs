@@ -0,0 +1,403 @@
+use crate::config::{EntryKind, ScanResult};
+use crate::error::DeleterError;
+use crate::scan::ScanSummary;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Plan file format version written by this build
+pub const PLAN_VERSION: u32 = 2;
+
+/// What to do when a planned entry changed since the plan was created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DriftPolicy {
+    /// Refuse to apply the plan if any entry changed
+    #[default]
+    Abort,
+    /// Leave changed entries in place and delete the rest
+    Skip,
+}
+
+/// A selected entry with the metadata it had when the plan was created
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlanEntry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// Bytes freed (regular files only)
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Inode number (Unix only)
+    pub inode: Option<u64>,
+    /// Device of the filesystem holding it (Unix only; absent from older plans)
+    #[serde(default)]
+    pub device: Option<u64>,
+    /// Index into [`Plan::roots`]
+    pub root: usize,
+}
+
+/// Reviewable list of everything a delete run would remove
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Plan {
+    pub version: u32,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    /// Human-readable description of the include patterns
+    pub selection: String,
+    pub roots: Vec<PathBuf>,
+    /// Entries in scan order (files before the directories containing them)
+    pub entries: Vec<PlanEntry>,
+}
+
+/// How an entry differs from its planned state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    Missing,
+    KindChanged,
+    /// Same path, different inode
+    Replaced,
+    Resized {
+        planned: u64,
+        found: u64,
+    },
+    Modified,
+    Unreadable(String),
+    /// The duplicate being kept in its place changed, so this copy stays
+    KeptCopyChanged,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Missing => write!(f, "no longer exists"),
+            Drift::KindChanged => write!(f, "changed to a different kind of entry"),
+            Drift::Replaced => write!(f, "replaced by a different file"),
+            Drift::Resized { planned, found } => {
+                write!(f, "size changed from {} to {} bytes", planned, found)
+            }
+            Drift::Modified => write!(f, "modified since the plan was created"),
+            Drift::Unreadable(e) => write!(f, "cannot be checked: {}", e),
+            Drift::KeptCopyChanged => write!(f, "the copy being kept changed"),
+        }
+    }
+}
+
+/// A planned entry left in place because it changed
+#[derive(Debug, Clone)]
+pub struct DriftedItem {
+    pub path: PathBuf,
+    /// Index into [`crate::DeletionReport::roots`]
+    pub root: usize,
+    pub drift: Drift,
+}
+
+#[cfg(unix)]
+fn inode(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+impl PlanEntry {
+    /// Record the current metadata of a scanned entry (the entry itself, not a symlink target)
+    pub async fn capture(result: &ScanResult) -> std::io::Result<Self> {
+        let metadata = tokio::fs::symlink_metadata(&result.path).await?;
+        Ok(Self {
+            path: result.path.clone(),
+            kind: result.kind,
+            size: if result.kind == EntryKind::File {
+                metadata.len()
+            } else {
+                0
+            },
+            modified: metadata.modified().ok(),
+            inode: inode(&metadata),
+            device: device(&metadata),
+            root: result.root,
+        })
+    }
+
+    /// Compare the entry on disk against its planned state
+    pub async fn check(&self) -> Result<(), Drift> {
+        let metadata = match tokio::fs::symlink_metadata(&self.path).await {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(Drift::Missing),
+            Err(e) => return Err(Drift::Unreadable(e.to_string())),
+        };
+        if EntryKind::of(metadata.file_type()) != Some(self.kind) {
+            return Err(Drift::KindChanged);
+        }
+        if (self.inode.is_some() && inode(&metadata) != self.inode)
+            || (self.device.is_some() && device(&metadata) != self.device)
+        {
+            return Err(Drift::Replaced);
+        }
+        // A directory's mtime changes as its planned contents are removed, so only files are compared
+        if self.kind == EntryKind::Dir {
+            return Ok(());
+        }
+        if self.kind == EntryKind::File && metadata.len() != self.size {
+            return Err(Drift::Resized {
+                planned: self.size,
+                found: metadata.len(),
+            });
+        }
+        if metadata.modified().ok() != self.modified {
+            return Err(Drift::Modified);
+        }
+        Ok(())
+    }
+
+    /// Pipeline item for this entry, with the planned size
+    pub fn to_scan_result(&self) -> ScanResult {
+        ScanResult {
+            path: self.path.clone(),
+            kind: self.kind,
+            size: self.size,
+            root: self.root,
+            owner: None,
+        }
+    }
+}
+
+impl Plan {
+    /// Start an empty plan for the given roots
+    pub fn new(selection: String, roots: Vec<PathBuf>) -> Self {
+        Self {
+            version: PLAN_VERSION,
+            created_at: SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("System time went backwards")
+                .as_secs(),
+            selection,
+            roots,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Read a plan written by `spa plan`
+    pub async fn read(path: &Path) -> Result<Plan, DeleterError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let plan: Plan = serde_json::from_str(&content)
+            .map_err(|e| DeleterError::Plan(format!("{}: {}", path.display(), e)))?;
+        if plan.version != PLAN_VERSION {
+            return Err(DeleterError::Plan(format!(
+                "{}: unsupported plan version {} (expected {})",
+                path.display(),
+                plan.version,
+                PLAN_VERSION
+            )));
+        }
+        if let Some(entry) = plan.entries.iter().find(|e| e.root >= plan.roots.len()) {
+            return Err(DeleterError::Plan(format!(
+                "{}: entry {} refers to unknown root {}",
+                path.display(),
+                entry.path.display(),
+                entry.root
+            )));
+        }
+        Ok(plan)
+    }
+
+    /// Write the plan as pretty-printed JSON for review
+    pub async fn write(&self, path: &Path) -> Result<(), DeleterError> {
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| DeleterError::Plan(e.to_string()))?;
+        tokio::fs::write(path, json + "\n").await?;
+        Ok(())
+    }
+
+    /// Planned file, directory and byte totals
+    pub fn summary(&self) -> ScanSummary {
+        let mut summary = ScanSummary::default();
+        for entry in &self.entries {
+            summary.add(&entry.to_scan_result());
+        }
+        summary
+    }
+
+    /// Every entry that no longer matches its planned state
+    pub async fn verify(&self) -> Vec<DriftedItem> {
+        let mut drifted = Vec::new();
+        for entry in &self.entries {
+            if let Err(drift) = entry.check().await {
+                drifted.push(DriftedItem {
+                    path: entry.path.clone(),
+                    root: entry.root,
+                    drift,
+                });
+            }
+        }
+        drifted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    fn file(path: &Path) -> ScanResult {
+        ScanResult {
+            path: path.to_path_buf(),
+            kind: EntryKind::File,
+            size: fs::metadata(path).unwrap().len(),
+            root: 0,
+            owner: None,
+        }
+    }
+
+    async fn captured(path: &Path, content: &str) -> PlanEntry {
+        fs::write(path, content).unwrap();
+        PlanEntry::capture(&file(path)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn unchanged_entries_pass_the_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = captured(&dir.path().join("a.log"), "hello").await;
+        assert_eq!(entry.size, 5);
+        assert_eq!(entry.check().await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn check_reports_missing_and_retyped_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.log");
+        let entry = captured(&path, "hello").await;
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entry.check().await, Err(Drift::Missing));
+        fs::create_dir(&path).unwrap();
+        assert_eq!(entry.check().await, Err(Drift::KindChanged));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn check_reports_a_file_replaced_under_the_same_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.log");
+        let entry = captured(&path, "hello").await;
+        // Rename over the original so the new file cannot reuse its inode
+        let other = dir.path().join("b.log");
+        fs::write(&other, "hello").unwrap();
+        fs::rename(&other, &path).unwrap();
+        assert_eq!(entry.check().await, Err(Drift::Replaced));
+    }
+
+    #[tokio::test]
+    async fn check_reports_size_and_mtime_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.log");
+        let entry = captured(&path, "hello").await;
+
+        fs::write(&path, "hello world").unwrap();
+        assert_eq!(
+            entry.check().await,
+            Err(Drift::Resized {
+                planned: 5,
+                found: 11
+            })
+        );
+
+        fs::write(&path, "HELLO").unwrap();
+        let earlier = entry.modified.unwrap() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+        assert_eq!(entry.check().await, Err(Drift::Modified));
+    }
+
+    #[tokio::test]
+    async fn directories_ignore_mtime_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("cache");
+        fs::create_dir(&sub).unwrap();
+        let entry = PlanEntry::capture(&ScanResult {
+            path: sub.clone(),
+            kind: EntryKind::Dir,
+            size: 0,
+            root: 0,
+            owner: None,
+        })
+        .await
+        .unwrap();
+        fs::write(sub.join("new.tmp"), "x").unwrap();
+        fs::remove_file(sub.join("new.tmp")).unwrap();
+        assert_eq!(entry.check().await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn written_plans_read_back_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plan = Plan::new("*.log".to_string(), vec![dir.path().to_path_buf()]);
+        plan.entries
+            .push(captured(&dir.path().join("a.log"), "hello").await);
+        let path = dir.path().join("plan.json");
+        plan.write(&path).await.unwrap();
+
+        let read = Plan::read(&path).await.unwrap();
+        assert_eq!(read.version, PLAN_VERSION);
+        assert_eq!(read.created_at, plan.created_at);
+        assert_eq!(read.selection, "*.log");
+        assert_eq!(read.roots, plan.roots);
+        assert_eq!(read.entries.len(), 1);
+        let (a, b) = (&read.entries[0], &plan.entries[0]);
+        assert_eq!(
+            (
+                &a.path, a.kind, a.size, a.modified, a.inode, a.device, a.root
+            ),
+            (
+                &b.path, b.kind, b.size, b.modified, b.inode, b.device, b.root
+            )
+        );
+        assert!(read.verify().await.is_empty());
+    }
+
+    async fn read_error(dir: &Path, plan: &Plan) -> String {
+        let path = dir.join("plan.json");
+        plan.write(&path).await.unwrap();
+        Plan::read(&path).await.unwrap_err().to_string()
+    }
+
+    #[tokio::test]
+    async fn read_rejects_other_versions_and_unknown_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plan = Plan::new("*.log".to_string(), vec![dir.path().to_path_buf()]);
+        plan.version = PLAN_VERSION + 1;
+        assert!(
+            read_error(dir.path(), &plan)
+                .await
+                .contains("unsupported plan version")
+        );
+
+        plan.version = PLAN_VERSION;
+        let mut entry = captured(&dir.path().join("a.log"), "hello").await;
+        entry.root = 1;
+        plan.entries.push(entry);
+        assert!(
+            read_error(dir.path(), &plan)
+                .await
+                .contains("refers to unknown root 1")
+        );
+    }
+}
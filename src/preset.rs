@@ -0,0 +1,164 @@
+use crate::error::DeleterError;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Directories named `name` are build artifacts when their parent holds one of `markers`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirRule {
+    pub name: &'static str,
+    /// Sibling files that identify the project type (empty = match any directory with this name)
+    pub markers: &'static [&'static str],
+}
+
+/// A named set of include/exclude globs and directory rules
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub dirs: &'static [DirRule],
+    pub include: &'static [&'static str],
+    pub exclude: &'static [&'static str],
+}
+
+const fn dir(name: &'static str, markers: &'static [&'static str]) -> DirRule {
+    DirRule { name, markers }
+}
+
+/// Built-in presets
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "rust",
+        description: "Cargo build output",
+        dirs: &[dir("target", &["Cargo.toml"])],
+        include: &[],
+        exclude: &[],
+    },
+    Preset {
+        name: "node",
+        description: "npm/yarn/pnpm dependencies and bundler caches",
+        dirs: &[
+            dir("node_modules", &["package.json"]),
+            dir(".next", &["package.json"]),
+            dir(".nuxt", &["package.json"]),
+            dir(".parcel-cache", &[]),
+            dir(".turbo", &[]),
+        ],
+        include: &[],
+        exclude: &[],
+    },
+    Preset {
+        name: "python",
+        description: "Bytecode and tool caches",
+        dirs: &[
+            dir("__pycache__", &[]),
+            dir(".pytest_cache", &[]),
+            dir(".mypy_cache", &[]),
+            dir(".ruff_cache", &[]),
+            dir(".tox", &["tox.ini", "pyproject.toml", "setup.py"]),
+        ],
+        include: &["**/*.pyc", "**/*.pyo"],
+        exclude: &["**/site-packages/**"],
+    },
+    Preset {
+        name: "gradle",
+        description: "Gradle caches and build output",
+        dirs: &[
+            dir(".gradle", &[]),
+            dir(
+                "build",
+                &[
+                    "build.gradle",
+                    "build.gradle.kts",
+                    "settings.gradle",
+                    "settings.gradle.kts",
+                ],
+            ),
+        ],
+        include: &[],
+        exclude: &[],
+    },
+    Preset {
+        name: "maven",
+        description: "Maven build output",
+        dirs: &[dir("target", &["pom.xml"])],
+        include: &[],
+        exclude: &[],
+    },
+    Preset {
+        name: "c",
+        description: "C/C++ object files",
+        dirs: &[],
+        include: &["**/*.o", "**/*.obj"],
+        exclude: &[],
+    },
+    Preset {
+        name: "os-junk",
+        description: "Desktop metadata files",
+        dirs: &[],
+        include: &["**/.DS_Store", "**/Thumbs.db", "**/desktop.ini"],
+        exclude: &[],
+    },
+];
+
+/// Look up a preset by name
+pub fn find_preset(name: &str) -> Result<&'static Preset, DeleterError> {
+    PRESETS.iter().find(|p| p.name == name).ok_or_else(|| {
+        let known: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+        DeleterError::Config(format!(
+            "unknown preset '{}' (available: {})",
+            name,
+            known.join(", ")
+        ))
+    })
+}
+
+/// Directory rules from the selected presets, with cached marker lookups
+#[derive(Debug, Default)]
+pub struct DirRules {
+    rules: Vec<DirRule>,
+    markers_seen: Mutex<HashMap<(std::path::PathBuf, &'static str), bool>>,
+}
+
+impl DirRules {
+    pub fn new(rules: Vec<DirRule>) -> Self {
+        Self {
+            rules,
+            markers_seen: Mutex::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// True if `path` is, or lies inside, a directory matched by a rule
+    pub fn contains(&self, path: &Path) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        path.ancestors().any(|ancestor| self.is_match(ancestor))
+    }
+
+    fn is_match(&self, dir: &Path) -> bool {
+        let (Some(name), Some(parent)) = (dir.file_name(), dir.parent()) else {
+            return false;
+        };
+        self.rules
+            .iter()
+            .filter(|rule| name == rule.name)
+            .any(|rule| {
+                rule.markers.is_empty() || rule.markers.iter().any(|m| self.has_marker(parent, m))
+            })
+    }
+
+    fn has_marker(&self, parent: &Path, marker: &'static str) -> bool {
+        let key = (parent.to_path_buf(), marker);
+        if let Some(&seen) = self.markers_seen.lock().unwrap().get(&key) {
+            return seen;
+        }
+        let exists = parent.join(marker).exists();
+        self.markers_seen.lock().unwrap().insert(key, exists);
+        exists
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// System locations that are never deleted from, along with everything beneath them
+#[cfg(not(windows))]
+pub const PROTECTED_TREES: &[&str] = &[
+    "/bin",
+    "/boot",
+    "/dev",
+    "/etc",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/proc",
+    "/run",
+    "/sbin",
+    "/sys",
+    "/usr",
+    "/var/lib",
+    "/Applications",
+    "/Library",
+    "/System",
+];
+
+/// System locations that are never deleted from, along with everything beneath them
+#[cfg(windows)]
+pub const PROTECTED_TREES: &[&str] = &[
+    "C:\\Windows",
+    "C:\\Program Files",
+    "C:\\Program Files (x86)",
+    "C:\\ProgramData",
+];
+
+/// Locations that may be cleaned inside but are never targets themselves
+/// (the current user's home directory is added at runtime)
+#[cfg(not(windows))]
+pub const PROTECTED_DIRS: &[&str] = &["/home", "/media", "/mnt", "/opt", "/root", "/Users", "/var"];
+
+/// Locations that may be cleaned inside but are never targets themselves
+/// (the current user's home directory is added at runtime)
+#[cfg(windows)]
+pub const PROTECTED_DIRS: &[&str] = &["C:\\Users"];
+
+/// Protected-path denylist, compared against canonical paths
+#[derive(Debug, Default)]
+pub struct ProtectedPaths {
+    /// Protected along with everything beneath them
+    trees: Vec<PathBuf>,
+    /// Protected themselves only
+    dirs: Vec<PathBuf>,
+}
+
+impl ProtectedPaths {
+    /// Built-in denylist plus user-configured trees
+    pub fn new(extra: &[PathBuf]) -> Self {
+        let trees = PROTECTED_TREES
+            .iter()
+            .map(PathBuf::from)
+            .chain(extra.iter().cloned());
+        let dirs = PROTECTED_DIRS
+            .iter()
+            .map(PathBuf::from)
+            .chain(dirs::home_dir());
+        Self {
+            trees: with_canonical(trees),
+            dirs: with_canonical(dirs),
+        }
+    }
+
+    /// Empty list (protection overridden)
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty() && self.dirs.is_empty()
+    }
+
+    /// True if `path` must not be deleted
+    pub fn contains(&self, path: &Path) -> bool {
+        !self.is_empty() && self.holds(&resolve(path))
+    }
+
+    /// True if `path` lies in a protected tree, so nothing beneath it needs scanning
+    pub fn covers(&self, path: &Path) -> bool {
+        !self.trees.is_empty() && self.in_tree(&resolve(path))
+    }
+
+    /// Checks for one directory walk, canonicalizing each parent directory only once
+    pub(crate) fn walk(&self) -> ProtectedWalk<'_> {
+        ProtectedWalk {
+            protected: self,
+            parents: RefCell::default(),
+        }
+    }
+
+    fn holds(&self, resolved: &Path) -> bool {
+        self.dirs.iter().any(|d| d == resolved) || self.in_tree(resolved)
+    }
+
+    fn in_tree(&self, resolved: &Path) -> bool {
+        self.trees.iter().any(|t| resolved.starts_with(t))
+    }
+}
+
+/// [`ProtectedPaths`] checks for the entries of one walk; the parent cache is dropped with it
+pub(crate) struct ProtectedWalk<'a> {
+    protected: &'a ProtectedPaths,
+    /// Canonical form of each parent directory seen in this walk
+    parents: RefCell<HashMap<PathBuf, PathBuf>>,
+}
+
+impl ProtectedWalk<'_> {
+    /// Same as [`ProtectedPaths::contains`]
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        !self.protected.is_empty() && self.protected.holds(&self.resolve(path))
+    }
+
+    /// Same as [`ProtectedPaths::covers`]
+    pub(crate) fn covers(&self, path: &Path) -> bool {
+        !self.protected.trees.is_empty() && self.protected.in_tree(&self.resolve(path))
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return resolve(path);
+        };
+        if let Some(canonical) = self.parents.borrow().get(parent) {
+            return canonical.join(name);
+        }
+        let canonical = canonical_dir(parent);
+        let resolved = canonical.join(name);
+        self.parents
+            .borrow_mut()
+            .insert(parent.to_path_buf(), canonical);
+        resolved
+    }
+}
+
+/// Absolute path with the parent directory canonicalized (the entry itself is not followed)
+fn resolve(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => canonical_dir(parent).join(name),
+        _ => std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+/// Canonical form of a directory, or its absolute form if it cannot be resolved
+fn canonical_dir(dir: &Path) -> PathBuf {
+    std::fs::canonicalize(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    })
+    .or_else(|_| std::path::absolute(dir))
+    .unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Each path plus its canonical form when that differs (e.g. `/etc` -> `/private/etc`)
+fn with_canonical(paths: impl Iterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    for path in paths {
+        if let Ok(canonical) = std::fs::canonicalize(&path) {
+            if canonical != path && !out.contains(&canonical) {
+                out.push(canonical);
+            }
+        }
+        if !out.contains(&path) {
+            out.push(path);
+        }
+    }
+    out
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trees_are_covered_but_dirs_only_protect_themselves() {
+        let protected = ProtectedPaths::new(&[]);
+        assert!(protected.contains(Path::new("/usr")));
+        assert!(protected.contains(Path::new("/usr/share/doc")));
+        assert!(protected.covers(Path::new("/usr")));
+        assert!(protected.covers(Path::new("/usr/share/doc")));
+
+        assert!(protected.contains(Path::new("/home")));
+        assert!(!protected.covers(Path::new("/home")));
+        assert!(!protected.contains(Path::new("/home/nobody-here/notes.txt")));
+        assert!(!protected.covers(Path::new("/home/nobody-here/notes.txt")));
+    }
+
+    #[test]
+    fn extra_trees_and_links_into_them_are_protected() {
+        let dir = tempfile::tempdir().unwrap();
+        let keep = dir.path().join("keep");
+        std::fs::create_dir(&keep).unwrap();
+        std::os::unix::fs::symlink(&keep, dir.path().join("link")).unwrap();
+        let protected = ProtectedPaths::new(std::slice::from_ref(&keep));
+
+        assert!(protected.covers(&keep.join("a.log")));
+        // The parent is resolved, so a path through the link lands in the tree...
+        assert!(protected.contains(&dir.path().join("link/a.log")));
+        // ...but the link itself is an entry of its own and may go
+        assert!(!protected.contains(&dir.path().join("link")));
+        assert!(!protected.contains(&dir.path().join("other.log")));
+    }
+
+    #[test]
+    fn walks_agree_with_uncached_checks() {
+        let protected = ProtectedPaths::new(&[]);
+        let walk = protected.walk();
+        for path in [
+            "/usr/bin",
+            "/usr/lib",
+            "/home",
+            "/home/nobody-here/a",
+            "/tmp/a",
+        ] {
+            let path = Path::new(path);
+            // Twice, so the second answer comes from the cached parent
+            for _ in 0..2 {
+                assert_eq!(walk.contains(path), protected.contains(path), "{:?}", path);
+                assert_eq!(walk.covers(path), protected.covers(path), "{:?}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_is_protected_when_overridden() {
+        let protected = ProtectedPaths::none();
+        assert!(!protected.contains(Path::new("/usr")));
+        assert!(!protected.walk().covers(Path::new("/usr/bin")));
+    }
+}
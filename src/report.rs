@@ -0,0 +1,183 @@
+use crate::limits::Limit;
+use crate::plan::DriftedItem;
+use crate::retention::KeptEntry;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// An item that could not be deleted, with the reason
+#[derive(Debug, Clone)]
+pub struct FailedItem {
+    pub path: PathBuf,
+    /// Index into [`DeletionReport::roots`]
+    pub root: usize,
+    pub kind: io::ErrorKind,
+    pub message: String,
+}
+
+impl FailedItem {
+    /// Build from an I/O error
+    pub fn from_io(path: PathBuf, root: usize, err: &io::Error) -> Self {
+        Self {
+            path,
+            root,
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Counts for a single input path (directory or individual file)
+#[derive(Debug, Clone, Default)]
+pub struct RootReport {
+    pub root: PathBuf,
+    pub deleted: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    pub bytes_freed: u64,
+}
+
+/// Free space before and after a run with a free-space target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSpaceReport {
+    /// Bytes that had to be available
+    pub goal: u64,
+    pub available_before: u64,
+    /// Measured after the run; estimated from the bytes that would be freed in a dry run
+    pub available_after: u64,
+    pub achieved: bool,
+}
+
+/// Where a run stopped by `--max-count`, `--max-bytes` or `--time-limit` left off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopReport {
+    pub limit: Limit,
+    /// Matches already found but not yet deleted when the run stopped; all the
+    /// matches left only if `scan_complete`
+    pub queued: u64,
+    pub queued_bytes: u64,
+    /// Matches passed over because they did not fit under `--max-bytes`
+    pub skipped: u64,
+    pub skipped_bytes: u64,
+    /// False if the scan was cut short too, so more matches may remain than counted
+    pub scan_complete: bool,
+}
+
+/// Outcome of a deletion run
+#[derive(Debug, Clone, Default)]
+pub struct DeletionReport {
+    /// Items deleted (or that would be deleted in dry-run mode)
+    pub deleted: u64,
+    pub failed: u64,
+    /// Items left in place on purpose (e.g. non-empty directories)
+    pub skipped: u64,
+    pub bytes_freed: u64,
+    pub failures: Vec<FailedItem>,
+    pub elapsed: Duration,
+    /// Per-root breakdown, in the order roots were processed
+    pub roots: Vec<RootReport>,
+    /// Recorded entries skipped because they changed (plan and dedupe runs only)
+    pub drifted: Vec<DriftedItem>,
+    /// Set when the run worked toward a free-space target
+    pub free_space: Option<FreeSpaceReport>,
+    /// Set when a run limit stopped the run early
+    pub stopped: Option<StopReport>,
+    /// Matches held back by retention, with why
+    pub kept: Vec<KeptEntry>,
+    pub dry_run: bool,
+}
+
+impl DeletionReport {
+    /// Root path a failure belongs to
+    pub fn root_of(&self, failure: &FailedItem) -> Option<&PathBuf> {
+        self.roots.get(failure.root).map(|r| &r.root)
+    }
+}
+
+/// Lock-free counters shared by pipeline workers
+#[derive(Default)]
+pub(crate) struct Tally {
+    deleted: AtomicU64,
+    failed: AtomicU64,
+    skipped: AtomicU64,
+    bytes_freed: AtomicU64,
+}
+
+/// Totals plus one [`Tally`] per root
+pub(crate) struct Counters {
+    total: Tally,
+    roots: Vec<(PathBuf, Tally)>,
+}
+
+impl Counters {
+    pub(crate) fn new(roots: &[PathBuf]) -> Self {
+        Self {
+            total: Tally::default(),
+            roots: roots
+                .iter()
+                .map(|r| (r.clone(), Tally::default()))
+                .collect(),
+        }
+    }
+
+    fn each(&self, root: usize, f: impl Fn(&Tally)) {
+        f(&self.total);
+        if let Some((_, tally)) = self.roots.get(root) {
+            f(tally);
+        }
+    }
+
+    pub(crate) fn deleted(&self, root: usize, bytes: u64) {
+        self.each(root, |t| {
+            t.deleted.fetch_add(1, Ordering::Relaxed);
+            t.bytes_freed.fetch_add(bytes, Ordering::Relaxed);
+        });
+    }
+
+    pub(crate) fn failed(&self, root: usize) {
+        self.each(root, |t| {
+            t.failed.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    pub(crate) fn skipped(&self, root: usize) {
+        self.each(root, |t| {
+            t.skipped.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Snapshot the counters into a report
+    pub(crate) fn into_report(
+        self,
+        failures: Vec<FailedItem>,
+        elapsed: Duration,
+        dry_run: bool,
+    ) -> DeletionReport {
+        let load = |a: &AtomicU64| a.load(Ordering::Relaxed);
+        DeletionReport {
+            deleted: load(&self.total.deleted),
+            failed: load(&self.total.failed),
+            skipped: load(&self.total.skipped),
+            bytes_freed: load(&self.total.bytes_freed),
+            failures,
+            elapsed,
+            roots: self
+                .roots
+                .iter()
+                .map(|(root, t)| RootReport {
+                    root: root.clone(),
+                    deleted: load(&t.deleted),
+                    failed: load(&t.failed),
+                    skipped: load(&t.skipped),
+                    bytes_freed: load(&t.bytes_freed),
+                })
+                .collect(),
+            drifted: Vec::new(),
+            free_space: None,
+            stopped: None,
+            kept: Vec::new(),
+            dry_run,
+        }
+    }
+}
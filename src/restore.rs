@@ -0,0 +1,235 @@
+use crate::error::DeleterError;
+use crate::log::{DeletedItem, read_log};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use trash::TrashItem;
+
+/// How to handle an existing item at a restore target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Leave the existing item alone and keep the trashed one in the trash
+    #[default]
+    Skip,
+    /// Restore next to the existing item under a new name (`name.restored`)
+    Rename,
+    /// Replace the existing item with the trashed one
+    Overwrite,
+}
+
+/// Outcome of restoring a log
+#[derive(Debug, Clone, Default)]
+pub struct RestoreReport {
+    /// Items restored to their original path
+    pub restored: u64,
+    /// Items restored under a new name because of a conflict
+    pub renamed: Vec<PathBuf>,
+    /// Items that replaced an existing item
+    pub overwritten: u64,
+    /// Items left in the trash because the target already exists
+    pub conflicts: Vec<PathBuf>,
+    /// Logged items with no matching trash entry
+    pub missing: Vec<PathBuf>,
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+/// Trash entries deleted this many seconds away from the log timestamp are not matched
+const MATCH_WINDOW_SECS: i64 = 120;
+
+/// Restore every trashed item recorded in a spacefree NDJSON log
+pub async fn restore_from_log(
+    log_path: &Path,
+    policy: ConflictPolicy,
+    dry_run: bool,
+) -> Result<RestoreReport, DeleterError> {
+    let items = read_log(log_path).await?;
+    tokio::task::spawn_blocking(move || restore_items(items, policy, dry_run))
+        .await
+        .map_err(|_| DeleterError::Join)?
+}
+
+fn restore_items(
+    items: Vec<DeletedItem>,
+    policy: ConflictPolicy,
+    dry_run: bool,
+) -> Result<RestoreReport, DeleterError> {
+    let mut in_trash: HashMap<PathBuf, Vec<TrashItem>> = HashMap::new();
+    for item in platform::list().map_err(DeleterError::Restore)? {
+        in_trash.entry(item.original_path()).or_default().push(item);
+    }
+
+    let mut report = RestoreReport::default();
+
+    // Restore files before directories so parents are recreated by their children
+    let (dirs, files): (Vec<_>, Vec<_>) = items.into_iter().partition(|i| i.is_dir);
+    for logged in files.into_iter().chain(dirs) {
+        let target = resolve_logged_path(&logged.path);
+
+        // Pick the trash entry deleted closest to the logged time
+        let candidates = in_trash.get_mut(&target);
+        let best = candidates.and_then(|list| {
+            let (index, _) = list
+                .iter()
+                .enumerate()
+                .map(|(i, t)| (i, (t.time_deleted - logged.deleted_at as i64).abs()))
+                .filter(|(i, diff)| list[*i].time_deleted < 0 || *diff <= MATCH_WINDOW_SECS)
+                .min_by_key(|(_, diff)| *diff)?;
+            Some(list.swap_remove(index))
+        });
+        let Some(trashed) = best else {
+            if !logged.is_dir {
+                warn!("Not found in trash: {}", target.display());
+                report.missing.push(target);
+            }
+            continue;
+        };
+
+        let conflict = target.symlink_metadata().is_ok();
+        if dry_run {
+            match (conflict, policy) {
+                (false, _) => report.restored += 1,
+                (true, ConflictPolicy::Skip) => report.conflicts.push(target),
+                (true, ConflictPolicy::Rename) => report.renamed.push(renamed_target(&target)),
+                (true, ConflictPolicy::Overwrite) => report.overwritten += 1,
+            }
+            continue;
+        }
+
+        let result = match (conflict, policy) {
+            (false, _) => platform::restore(trashed).map(|_| report.restored += 1),
+            (true, ConflictPolicy::Skip) => {
+                info!("Target exists, leaving in trash: {}", target.display());
+                report.conflicts.push(target);
+                continue;
+            }
+            (true, ConflictPolicy::Rename) => {
+                restore_renamed(trashed, &target).map(|new_path| report.renamed.push(new_path))
+            }
+            (true, ConflictPolicy::Overwrite) => remove_existing(&target)
+                .map_err(|e| e.to_string())
+                .and_then(|_| platform::restore(trashed))
+                .map(|_| report.overwritten += 1),
+        };
+
+        match result {
+            Ok(()) => info!("Restored: {}", target.display()),
+            Err(e) => {
+                warn!("Failed to restore {}: {}", target.display(), e);
+                report.failures.push((target, e));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Number of items currently in the trash (fails where listing is unsupported)
+pub async fn trash_item_count() -> Result<usize, DeleterError> {
+    tokio::task::spawn_blocking(|| platform::list().map(|items| items.len()))
+        .await
+        .map_err(|_| DeleterError::Join)?
+        .map_err(DeleterError::Restore)
+}
+
+/// Match the trash's view of a path: absolute, with a canonical parent
+fn resolve_logged_path(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => std::fs::canonicalize(parent)
+            .map(|p| p.join(name))
+            .unwrap_or(absolute),
+        _ => absolute,
+    }
+}
+
+/// First free sibling name: `name.restored`, `name.restored.2`, ...
+fn renamed_target(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut candidate = target.with_file_name(format!("{name}.restored"));
+    let mut counter = 2;
+    while candidate.symlink_metadata().is_ok() {
+        candidate = target.with_file_name(format!("{name}.restored.{counter}"));
+        counter += 1;
+    }
+    candidate
+}
+
+/// Restore under a new name by moving the existing item aside for the duration
+fn restore_renamed(trashed: TrashItem, target: &Path) -> Result<PathBuf, String> {
+    let new_path = renamed_target(target);
+    let aside = renamed_target(&target.with_extension("spacefree-aside"));
+
+    std::fs::rename(target, &aside).map_err(|e| e.to_string())?;
+    if let Err(e) = platform::restore(trashed) {
+        std::fs::rename(&aside, target).map_err(|e| e.to_string())?;
+        return Err(e);
+    }
+    if let Err(e) = std::fs::rename(target, &new_path) {
+        // Never move the existing item back over the restored one
+        return Err(format!(
+            "restored to {} but could not rename it ({}); existing item moved to {}",
+            target.display(),
+            e,
+            aside.display()
+        ));
+    }
+    std::fs::rename(&aside, target).map_err(|e| e.to_string())?;
+
+    Ok(new_path)
+}
+
+fn remove_existing(target: &Path) -> std::io::Result<()> {
+    if target.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir_all(target)
+    } else {
+        std::fs::remove_file(target)
+    }
+}
+
+/// Trash listing and restore are only available on Windows and freedesktop systems
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+mod platform {
+    use trash::{TrashItem, os_limited};
+
+    pub fn list() -> Result<Vec<TrashItem>, String> {
+        os_limited::list().map_err(|e| e.to_string())
+    }
+
+    pub fn restore(item: TrashItem) -> Result<(), String> {
+        os_limited::restore_all([item]).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+mod platform {
+    use trash::TrashItem;
+
+    const UNSUPPORTED: &str = "restoring from trash is not supported on this platform";
+
+    pub fn list() -> Result<Vec<TrashItem>, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn restore(_item: TrashItem) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
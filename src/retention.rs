@@ -0,0 +1,526 @@
+use crate::config::{DeleteConfig, ScanResult};
+use crate::error::DeleterError;
+use chrono::{DateTime, Local};
+use globset::{Glob, GlobMatcher};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::debug;
+
+/// What a retention rule ranks entries by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetainBy {
+    /// Most recent first, by the timestamp the age filters use
+    Newest,
+    /// Largest first
+    Largest,
+}
+
+impl fmt::Display for RetainBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetainBy::Newest => write!(f, "newest"),
+            RetainBy::Largest => write!(f, "largest"),
+        }
+    }
+}
+
+/// File name glob whose matches in one directory are counted together, e.g. `app-*.log`
+#[derive(Debug, Clone)]
+pub struct GroupPattern {
+    pattern: String,
+    matcher: GlobMatcher,
+}
+
+impl GroupPattern {
+    pub fn new(pattern: &str) -> Result<Self, DeleterError> {
+        let matcher = Glob::new(pattern)
+            .map_err(|e| DeleterError::Glob(e.to_string()))?
+            .compile_matcher();
+        Ok(Self {
+            pattern: pattern.to_string(),
+            matcher,
+        })
+    }
+}
+
+/// Calendar period a grandfather-father-son rule keeps one entry per
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Period {
+    /// Local calendar bucket of a timestamp, e.g. `2026-03-14`, `2026-W11`, `2026-03`, `2026`
+    fn bucket(&self, time: SystemTime) -> String {
+        let local = DateTime::<Local>::from(time);
+        let format = match self {
+            Period::Daily => "%Y-%m-%d",
+            Period::Weekly => "%G-W%V",
+            Period::Monthly => "%Y-%m",
+            Period::Yearly => "%Y",
+        };
+        local.format(format).to_string()
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Period::Daily => write!(f, "daily"),
+            Period::Weekly => write!(f, "weekly"),
+            Period::Monthly => write!(f, "monthly"),
+            Period::Yearly => write!(f, "yearly"),
+        }
+    }
+}
+
+/// Grandfather-father-son rotation: the newest entry in each of the last N
+/// days, ISO weeks, months and years that have one (0 = period unused)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gfs {
+    pub daily: usize,
+    pub weekly: usize,
+    pub monthly: usize,
+    pub yearly: usize,
+}
+
+impl Gfs {
+    pub fn is_empty(&self) -> bool {
+        self.periods().iter().all(|(_, count)| *count == 0)
+    }
+
+    fn periods(&self) -> [(Period, usize); 4] {
+        [
+            (Period::Daily, self.daily),
+            (Period::Weekly, self.weekly),
+            (Period::Monthly, self.monthly),
+            (Period::Yearly, self.yearly),
+        ]
+    }
+}
+
+/// Matches held back from deletion in each group: the first `count` ranked by
+/// `by`, plus whatever the `gfs` periods keep.
+///
+/// Groups are directories, or with `groups` set, the names matching each
+/// pattern within a directory; matches outside every pattern are kept.
+#[derive(Debug, Clone)]
+pub struct Retention {
+    pub count: usize,
+    pub by: RetainBy,
+    /// Only used with [`RetainBy::Newest`], since periods need timestamps
+    pub gfs: Gfs,
+    pub groups: Vec<GroupPattern>,
+}
+
+impl Retention {
+    /// Directory and pattern index an entry is counted under
+    fn group_of(&self, path: &Path) -> Option<(PathBuf, usize)> {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        if self.groups.is_empty() {
+            return Some((dir, 0));
+        }
+        let name = path.file_name()?;
+        self.groups
+            .iter()
+            .position(|group| group.matcher.is_match(name))
+            .map(|index| (dir, index))
+    }
+
+    /// Human-readable group, for reporting why an entry was kept
+    fn describe(&self, (dir, index): &(PathBuf, usize)) -> String {
+        match self.groups.get(*index) {
+            Some(group) => format!("{} in {}", group.pattern, dir.display()),
+            None => dir.display().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rules = Vec::new();
+        if self.count > 0 {
+            rules.push(format!("{} {}", self.by, self.count));
+        }
+        for (period, count) in self.gfs.periods() {
+            if count > 0 {
+                rules.push(format!("{} {}", period, count));
+            }
+        }
+        write!(f, "keep {}", rules.join(" + "))?;
+        if self.groups.is_empty() {
+            write!(f, " per directory")
+        } else {
+            let patterns: Vec<&str> = self.groups.iter().map(|g| g.pattern.as_str()).collect();
+            write!(f, " of {}", patterns.join(", "))
+        }
+    }
+}
+
+/// A match held back from deletion, with the rules that kept it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeptEntry {
+    pub path: PathBuf,
+    /// E.g. `newest 3` or `weekly 2026-W11`, or why it could not be ranked
+    pub reasons: Vec<String>,
+    /// Directory or name group it was ranked in; `None` outside every `--keep-group` pattern
+    pub group: Option<String>,
+}
+
+impl fmt::Display for KeptEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.path.display(), self.reasons.join(", "))?;
+        if let Some(group) = &self.group {
+            write!(f, "; {}", group)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Sort key within a group; one run only ever compares one variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Time(SystemTime),
+    Size(u64),
+}
+
+/// Entries left for deletion once each group's kept entries are held back,
+/// and the kept entries in path order
+pub(crate) fn select(
+    results: Vec<ScanResult>,
+    retention: &Retention,
+    config: &DeleteConfig,
+) -> (Vec<ScanResult>, Vec<KeptEntry>) {
+    let source = config.time_source();
+    let mut kept: Vec<Option<KeptEntry>> = vec![None; results.len()];
+    let mut groups: HashMap<(PathBuf, usize), Vec<(usize, Rank)>> = HashMap::new();
+    for (index, result) in results.iter().enumerate() {
+        if result.is_dir() {
+            continue;
+        }
+        let Some(key) = retention.group_of(&result.path) else {
+            kept[index] = Some(KeptEntry {
+                path: result.path.clone(),
+                reasons: vec!["no --keep-group pattern matches".to_string()],
+                group: None,
+            });
+            continue;
+        };
+        let rank = match retention.by {
+            RetainBy::Largest => Some(Rank::Size(result.size)),
+            RetainBy::Newest => source
+                .of_path(&result.path, config.follow_symlinks)
+                .map(Rank::Time),
+        };
+        match rank {
+            Some(rank) => groups.entry(key).or_default().push((index, rank)),
+            None => {
+                kept[index] = Some(KeptEntry {
+                    path: result.path.clone(),
+                    reasons: vec![format!("no {}", source.describe())],
+                    group: Some(retention.describe(&key)),
+                });
+            }
+        }
+    }
+
+    for (key, mut members) in groups {
+        // Best first; equal ranks keep the lexicographically first path
+        members.sort_by(|(a, rank_a), (b, rank_b)| {
+            rank_b
+                .cmp(rank_a)
+                .then_with(|| results[*a].path.cmp(&results[*b].path))
+        });
+
+        // Why each member is kept, e.g. "newest 3" or "weekly 2026-W11"
+        let mut reasons = vec![Vec::new(); members.len()];
+        for reason in reasons.iter_mut().take(retention.count) {
+            reason.push(format!("{} {}", retention.by, retention.count));
+        }
+        for (period, count) in retention.gfs.periods() {
+            let mut last = None;
+            let mut used = 0;
+            for (position, (_, rank)) in members.iter().enumerate() {
+                if used == count {
+                    break;
+                }
+                let Rank::Time(time) = rank else { break };
+                // Newest first, so the first member seen in a bucket is its newest
+                let bucket = period.bucket(*time);
+                if last.as_ref() != Some(&bucket) {
+                    reasons[position].push(format!("{} {}", period, bucket));
+                    last = Some(bucket);
+                    used += 1;
+                }
+            }
+        }
+
+        for ((index, _), reasons) in members.iter().zip(reasons) {
+            if !reasons.is_empty() {
+                kept[*index] = Some(KeptEntry {
+                    path: results[*index].path.clone(),
+                    reasons,
+                    group: Some(retention.describe(&key)),
+                });
+            }
+        }
+    }
+
+    let mut deleted = Vec::new();
+    let mut held = Vec::new();
+    for (result, kept) in results.into_iter().zip(kept) {
+        match kept {
+            Some(entry) => {
+                debug!("Keeping {}", entry);
+                held.push(entry);
+            }
+            None => deleted.push(result),
+        }
+    }
+    held.sort_by(|a, b| a.path.cmp(&b.path));
+    (deleted, held)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EntryKind;
+    use crate::{Spacefree, SpacefreeBuilder};
+    use chrono::TimeZone;
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    const DAY: u64 = 86_400;
+
+    /// A file of `size` bytes last modified `age` seconds ago
+    fn file(dir: &Path, name: &str, size: usize, age: u64) -> PathBuf {
+        file_at(
+            dir,
+            name,
+            size,
+            SystemTime::now() - Duration::from_secs(age),
+        )
+    }
+
+    fn file_at(dir: &Path, name: &str, size: usize, modified: SystemTime) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![b'x'; size]).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        path
+    }
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> SystemTime {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap().into()
+    }
+
+    fn scanned(paths: &[PathBuf]) -> Vec<ScanResult> {
+        paths
+            .iter()
+            .map(|path| {
+                let metadata = fs::metadata(path).unwrap();
+                ScanResult {
+                    path: path.clone(),
+                    kind: if metadata.is_dir() {
+                        EntryKind::Dir
+                    } else {
+                        EntryKind::File
+                    },
+                    size: if metadata.is_dir() { 0 } else { metadata.len() },
+                    root: 0,
+                    owner: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Paths left for deletion under the builder's retention rules
+    fn deleted(builder: SpacefreeBuilder, dir: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let spacefree: Spacefree = builder.path(dir).build().unwrap();
+        let config = spacefree.config();
+        let retention = config.retention.as_ref().unwrap();
+        select(scanned(paths), retention, config)
+            .0
+            .into_iter()
+            .map(|result| result.path)
+            .collect()
+    }
+
+    #[test]
+    fn keep_newest_counts_each_directory_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths = [
+            file(d, "a/1.log", 1, 3 * DAY),
+            file(d, "a/2.log", 1, 2 * DAY),
+            file(d, "a/3.log", 1, DAY),
+            file(d, "b/1.log", 1, 9 * DAY),
+        ];
+        let left = deleted(Spacefree::builder().keep_newest(2), d, &paths);
+        assert_eq!(left, [paths[0].clone()]);
+    }
+
+    #[test]
+    fn keep_newest_holds_back_exactly_n() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths: Vec<PathBuf> = (1..=5)
+            .map(|i| file(d, &format!("{}.log", i), 1, i * DAY))
+            .collect();
+        let left = deleted(Spacefree::builder().keep_newest(3), d, &paths);
+        assert_eq!(left, &paths[3..]);
+        // Fewer matches than the count keeps them all
+        let left = deleted(Spacefree::builder().keep_newest(9), d, &paths);
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    fn name_groups_are_counted_apart_and_other_names_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths = [
+            file(d, "app-1.log", 30, DAY),
+            file(d, "app-2.log", 20, DAY),
+            file(d, "db-1.log", 5, DAY),
+            file(d, "db-2.log", 50, DAY),
+            file(d, "notes.txt", 1, DAY),
+        ];
+        let builder = Spacefree::builder()
+            .keep_largest(1)
+            .keep_group("app-*.log")
+            .keep_group("db-*.log");
+        let left = deleted(builder, d, &paths);
+        assert_eq!(left, [paths[1].clone(), paths[2].clone()]);
+    }
+
+    #[test]
+    fn ties_keep_the_first_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths = [
+            file(d, "b.bin", 10, DAY),
+            file(d, "a.bin", 10, DAY),
+            file(d, "c.bin", 10, DAY),
+        ];
+        let left = deleted(Spacefree::builder().keep_largest(1), d, &paths);
+        assert_eq!(left, [paths[0].clone(), paths[2].clone()]);
+    }
+
+    #[test]
+    fn directories_are_never_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths = [file(d, "sub/a.log", 1, DAY), d.join("sub")];
+        let left = deleted(Spacefree::builder().keep_newest(1), d, &paths);
+        assert_eq!(left, [d.join("sub")]);
+    }
+
+    #[test]
+    fn buckets_follow_local_calendar_and_iso_weeks() {
+        crate::pin_local_time_zone();
+        let bucket = |period: Period, time| period.bucket(time);
+        let new_years_eve = local(2025, 12, 31, 23, 30);
+        assert_eq!(bucket(Period::Daily, new_years_eve), "2025-12-31");
+        assert_eq!(bucket(Period::Monthly, new_years_eve), "2025-12");
+        assert_eq!(bucket(Period::Yearly, new_years_eve), "2025");
+        // ISO weeks start on Monday and belong to the year holding their Thursday
+        assert_eq!(bucket(Period::Weekly, new_years_eve), "2026-W01");
+        assert_eq!(
+            bucket(Period::Weekly, local(2024, 12, 30, 0, 0)),
+            "2025-W01"
+        );
+        assert_eq!(bucket(Period::Weekly, local(2027, 1, 3, 12, 0)), "2026-W53");
+        assert_eq!(
+            bucket(Period::Weekly, local(2026, 10, 18, 23, 59)),
+            "2026-W42"
+        );
+        assert_eq!(
+            bucket(Period::Weekly, local(2026, 10, 19, 0, 0)),
+            "2026-W43"
+        );
+    }
+
+    #[test]
+    fn gfs_periods_keep_the_newest_of_each_recent_bucket() {
+        crate::pin_local_time_zone();
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths = [
+            file_at(d, "a.tar", 1, local(2026, 10, 16, 12, 0)),
+            file_at(d, "b.tar", 1, local(2026, 10, 16, 8, 0)),
+            file_at(d, "c.tar", 1, local(2026, 10, 15, 12, 0)),
+            file_at(d, "d.tar", 1, local(2026, 10, 12, 12, 0)),
+            file_at(d, "e.tar", 1, local(2026, 10, 11, 12, 0)),
+            file_at(d, "f.tar", 1, local(2026, 9, 30, 12, 0)),
+            file_at(d, "g.tar", 1, local(2025, 12, 31, 12, 0)),
+        ];
+        let spacefree = Spacefree::builder()
+            .keep_newest(1)
+            .keep_daily(2)
+            .keep_weekly(2)
+            .keep_monthly(2)
+            .keep_yearly(2)
+            .path(d)
+            .build()
+            .unwrap();
+        let config = spacefree.config();
+        let (left, kept) = select(scanned(&paths), config.retention.as_ref().unwrap(), config);
+
+        let left: Vec<&Path> = left.iter().map(|r| r.path.as_path()).collect();
+        assert_eq!(left, [paths[1].as_path(), paths[3].as_path()]);
+        let group = d.display().to_string();
+        let expected = [
+            (
+                &paths[0],
+                &[
+                    "newest 1",
+                    "daily 2026-10-16",
+                    "weekly 2026-W42",
+                    "monthly 2026-10",
+                    "yearly 2026",
+                ][..],
+            ),
+            (&paths[2], &["daily 2026-10-15"]),
+            (&paths[4], &["weekly 2026-W41"]),
+            (&paths[5], &["monthly 2026-09"]),
+            (&paths[6], &["yearly 2025"]),
+        ];
+        assert_eq!(kept.len(), expected.len());
+        for (entry, (path, reasons)) in kept.iter().zip(expected) {
+            assert_eq!(&entry.path, path);
+            assert_eq!(entry.reasons, reasons);
+            assert_eq!(entry.group.as_deref(), Some(group.as_str()));
+        }
+    }
+
+    #[test]
+    fn ungrouped_matches_are_kept_with_a_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let paths = [file(d, "app-1.log", 1, DAY), file(d, "notes.txt", 1, DAY)];
+        let spacefree = Spacefree::builder()
+            .keep_newest(0)
+            .keep_daily(1)
+            .keep_group("app-*.log")
+            .path(d)
+            .build()
+            .unwrap();
+        let config = spacefree.config();
+        let (left, kept) = select(scanned(&paths), config.retention.as_ref().unwrap(), config);
+        assert!(left.is_empty());
+        assert_eq!(kept[1].path, paths[1]);
+        assert_eq!(kept[1].group, None);
+        assert_eq!(kept[1].reasons, ["no --keep-group pattern matches"]);
+        assert!(kept[0].reasons[0].starts_with("daily "));
+    }
+}
@@ -1,11 +1,54 @@
-use crate::config::{DeleteConfig, ScanResult};
+use crate::config::{DeleteConfig, EntryKind, ScanResult};
 use crate::error::DeleterError;
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::{fs, sync::mpsc};
-use tracing::{info, warn};
+use crate::filter::{Candidate, FilterChain};
+use crate::gitignore::{IgnoreMode, IgnoreRules};
+use crate::limits::Limit;
+use crate::owner::Ownership;
+use crate::retention::{self, KeptEntry};
+use crate::space;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::{
+    fs,
+    sync::mpsc,
+    task::{JoinHandle, spawn_blocking},
+};
+use tracing::{debug, info, warn};
 use walkdir::WalkDir;
 
+/// Totals from a scan with all filters applied
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    pub files: u64,
+    pub dirs: u64,
+    pub bytes: u64,
+    /// Matches held back by retention, with why; not counted in the totals
+    pub kept: Vec<KeptEntry>,
+    /// Set when a run limit cut the scan short, so more may match
+    pub stopped: Option<Limit>,
+}
+
+impl ScanSummary {
+    /// Count one scan result
+    pub fn add(&mut self, result: &ScanResult) {
+        if result.is_dir() {
+            self.dirs += 1;
+        } else {
+            self.files += 1;
+            self.bytes += result.size;
+        }
+    }
+
+    /// Files plus directories
+    pub fn items(&self) -> u64 {
+        self.files + self.dirs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items() == 0
+    }
+}
+
 /// Parse paths from file content (comma/space/newline separated)
 pub fn parse_paths_from_content(content: &str) -> Vec<PathBuf> {
     let mut seen = std::collections::HashSet::new();
@@ -45,7 +88,8 @@ pub async fn collect_paths(
     }
 
     for path in input_paths {
-        let _metadata = fs::metadata(path).await?;
+        // The path itself must exist; a dangling symlink is still a valid argument
+        let _metadata = fs::symlink_metadata(path).await?;
         // Add both directories and files to all_paths
         if seen.insert(path.clone()) {
             all_paths.push(path.clone());
@@ -59,27 +103,151 @@ pub async fn collect_paths(
     Ok(all_paths)
 }
 
+/// Spawn one scanner per directory plus one for all individual files.
+///
+/// Root indices follow the order directories then individual files. With
+/// retention or a free-space target set, an extra task selects from the
+/// finished scan before anything is passed on; the matches retention holds
+/// back are added to `kept` before the first selected one is sent.
+pub fn spawn_scanners(
+    directories: Vec<PathBuf>,
+    individual_files: Vec<PathBuf>,
+    scan_tx: mpsc::Sender<ScanResult>,
+    kept: Arc<Mutex<Vec<KeptEntry>>>,
+    config: &Arc<DeleteConfig>,
+) -> Vec<JoinHandle<()>> {
+    // Retention and free-space targets rank whole scans, so they buffer before passing anything on
+    let (scan_tx, select_handle) = if config.buffers_selection() {
+        let (tx, rx) = mpsc::channel((config.parallelism * 8).max(64));
+        let handle = tokio::spawn(select_stage(rx, scan_tx, kept, config.clone()));
+        (tx, Some(handle))
+    } else {
+        (scan_tx, None)
+    };
+
+    let dir_count = directories.len();
+    let mut handles: Vec<_> = directories
+        .into_iter()
+        .enumerate()
+        .map(|(index, root)| {
+            let scan_tx = scan_tx.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                let _ = scan_to_channel(root, index, scan_tx, config).await;
+            })
+        })
+        .collect();
+
+    if !individual_files.is_empty() {
+        let individual_files = individual_files
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| (dir_count + i, path))
+            .collect();
+        let config = config.clone();
+        handles.push(tokio::spawn(async move {
+            let _ = scan_files_direct(individual_files, scan_tx, config).await;
+        }));
+    }
+    handles.extend(select_handle);
+
+    handles
+}
+
+/// Buffer the whole scan, hold back what retention keeps, then order and cut
+/// the rest for a free-space target. Scan order is kept otherwise.
+async fn select_stage(
+    mut scan_rx: mpsc::Receiver<ScanResult>,
+    scan_tx: mpsc::Sender<ScanResult>,
+    kept: Arc<Mutex<Vec<KeptEntry>>>,
+    config: Arc<DeleteConfig>,
+) {
+    let mut results = Vec::new();
+    loop {
+        // The deleter hangs up early when a run limit stops it
+        let next = tokio::select! {
+            next = scan_rx.recv() => next,
+            _ = scan_tx.closed() => return,
+        };
+        let Some(result) = next else { break };
+        if crate::is_shutdown_requested() {
+            return;
+        }
+        results.push(result);
+    }
+
+    let selected = spawn_blocking(move || {
+        if let Some(retention) = &config.retention {
+            let held;
+            (results, held) = retention::select(results, retention, &config);
+            kept.lock().unwrap().extend(held);
+        }
+        if let Some(target) = &config.free_target {
+            results = space::select(results, target, &config);
+        }
+        results
+    })
+    .await;
+    for result in selected.into_iter().flatten() {
+        if scan_tx.send(result).await.is_err() {
+            break;
+        }
+    }
+}
+
 /// Scan a directory tree and send matching files to the channel
 pub async fn scan_to_channel(
     root: PathBuf,
+    root_index: usize,
     file_tx: mpsc::Sender<ScanResult>,
     config: Arc<DeleteConfig>,
 ) -> Result<(), DeleterError> {
     tokio::task::spawn_blocking(move || {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("System time went backwards")
-            .as_secs();
-
+        let filters = FilterChain::from_config(&config);
+        debug!("Filters for {}: {:?}", root.display(), filters.names());
         let mut scan_dirs = Vec::new();
 
-        let walkdir = WalkDir::new(&root).follow_links(config.follow_symlinks);
-        for entry in walkdir.into_iter().filter_map(|e| e.ok()) {
+        let mut walkdir = WalkDir::new(&root)
+            .follow_links(config.follow_symlinks)
+            .min_depth(config.min_depth);
+        if let Some(depth) = config.max_depth {
+            walkdir = walkdir.max_depth(depth);
+        }
+        let protected = config.protected.walk();
+        let ignores = IgnoreFilter::new(config.ignore_mode, &root);
+        let root_device = if config.one_file_system {
+            std::fs::metadata(&root).ok().and_then(|m| device(&m))
+        } else {
+            None
+        };
+        let walker = walkdir.into_iter().filter_entry(|e| {
+            // Never descend into protected trees
+            let covered = protected.covers(e.path());
+            if covered {
+                debug!("Skipping protected path: {}", e.path().display());
+            }
+            // Nor onto other filesystems: mount points, bind mounts and what lies below them
+            let foreign = root_device.is_some_and(|dev| {
+                e.metadata()
+                    .ok()
+                    .and_then(|m| device(&m))
+                    .is_some_and(|d| d != dev)
+            });
+            if foreign {
+                debug!("Skipping other filesystem: {}", e.path().display());
+            }
+            !covered && !foreign && ignores.enter(e.path(), e.file_type().is_dir())
+        });
+        for entry in walker.filter_map(|e| e.ok()) {
             // Check for shutdown request
             if crate::is_shutdown_requested() {
                 info!("Shutdown requested, stopping scan early");
                 break;
             }
+            if file_tx.is_closed() {
+                debug!("Deletion stopped, ending scan of {}", root.display());
+                break;
+            }
 
             let path = entry.path();
 
@@ -88,7 +256,20 @@ pub async fn scan_to_channel(
                 continue;
             }
 
-            if entry.file_type().is_file() {
+            let Some(kind) = EntryKind::of(entry.file_type()) else {
+                continue;
+            };
+
+            if kind != EntryKind::Dir {
+                let selected = config.selects(kind)
+                    || (kind == EntryKind::Symlink
+                        && config.broken_symlinks
+                        && std::fs::metadata(path).is_err());
+                if !selected {
+                    continue;
+                }
+
+                // Not followed, so links are judged by their own metadata
                 let metadata = match entry.metadata() {
                     Ok(m) => m,
                     Err(e) => {
@@ -97,73 +278,56 @@ pub async fn scan_to_channel(
                     }
                 };
 
-                let len = metadata.len();
-                if len < config.min_size {
-                    continue;
-                }
-                if let Some(max) = config.max_size {
-                    if len > max {
-                        continue;
-                    }
-                }
-
-                if let Ok(modified) = metadata.modified() {
-                    let modified_secs = modified
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .map(|d| d.as_secs())
-                        .unwrap_or(now);
-                    let age = now.saturating_sub(modified_secs);
-                    if let Some(min_age_val) = config.min_age {
-                        if age < min_age_val {
-                            continue;
-                        }
-                    }
-                    if let Some(max_age_val) = config.max_age {
-                        if age > max_age_val {
-                            continue;
-                        }
-                    }
-                }
-
-                // Check glob pattern for files (skip if using default "**/*" pattern)
-                if !config.skip_glob_match && !config.glob_matcher.is_match(path) {
+                if !ignores.select(path, false)
+                    || !filters.matches(&Candidate {
+                        path,
+                        root: &root,
+                        metadata: &metadata,
+                    })
+                {
                     continue;
                 }
 
-                if let Some(ref exclude) = config.exclude_matcher {
-                    if exclude.is_match(path) {
-                        continue;
-                    }
-                }
-
                 if file_tx
                     .blocking_send(ScanResult {
                         path: path.to_path_buf(),
-                        is_dir: false,
-                        size: len,
+                        kind,
+                        size: freed(kind, &metadata),
+                        root: root_index,
+                        owner: owner_of(&config, &metadata),
                     })
                     .is_err()
                 {
                     break;
                 }
-            } else if config.dirs && entry.file_type().is_dir() {
-                // Include ALL directories when --dirs is enabled
+            } else if (config.dirs || config.dir_rules.contains(path))
+                && !protected.contains(path)
+                && ignores.select(path, true)
+            {
+                // Include ALL directories when --dirs is enabled, otherwise only preset directories
                 // Don't filter by glob - only files need glob matching
                 scan_dirs.push(ScanResult {
                     path: path.to_path_buf(),
-                    is_dir: true,
+                    kind: EntryKind::Dir,
                     size: 0,
+                    root: root_index,
+                    owner: None,
                 });
             }
         }
 
         // After WalkDir completes, add the root directory if --dirs is enabled
         // This ensures WalkDir has fully released the directory before we try to delete it
-        if config.dirs {
+        if (config.dirs || config.dir_rules.contains(&root))
+            && config.min_depth == 0
+            && ignores.select(&root, true)
+        {
             scan_dirs.push(ScanResult {
                 path: root.to_path_buf(),
-                is_dir: true,
+                kind: EntryKind::Dir,
                 size: 0,
+                root: root_index,
+                owner: None,
             });
         }
 
@@ -186,59 +350,68 @@ pub async fn scan_to_channel(
     Ok(())
 }
 
-/// Scan individual files directly
+/// Scan individual files directly (each paired with its root index)
 pub async fn scan_files_direct(
-    paths: Vec<PathBuf>,
+    paths: Vec<(usize, PathBuf)>,
     file_tx: mpsc::Sender<ScanResult>,
     config: Arc<DeleteConfig>,
 ) -> Result<(), DeleterError> {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .expect("System time went backwards")
-        .as_secs();
+    let filters = FilterChain::from_config(&config);
+
+    // Individual files are roots themselves, at depth 0
+    if config.min_depth > 0 {
+        return Ok(());
+    }
+
+    for (root, path) in paths {
+        if config.protected.contains(&path) {
+            debug!("Skipping protected path: {}", path.display());
+            continue;
+        }
+
+        // Rebuilt per file: each may live in a different tree
+        if !IgnoreFilter::new(config.ignore_mode, &path).select(&path, false) {
+            debug!("Skipping by ignore rules: {}", path.display());
+            continue;
+        }
 
-    for path in paths {
-        let metadata = match fs::metadata(&path).await {
+        // Symbolic links given directly are links, judged by their own metadata
+        let metadata = match fs::symlink_metadata(&path).await {
             Ok(m) => m,
             Err(e) => {
                 warn!("Failed to read metadata for {}: {}", path.display(), e);
                 continue;
             }
         };
-
-        let len = metadata.len();
-        if len < config.min_size {
+        let Some(kind) = EntryKind::of(metadata.file_type()) else {
+            continue;
+        };
+        let selected = config.selects(kind)
+            || (kind == EntryKind::Symlink
+                && config.broken_symlinks
+                && fs::metadata(&path).await.is_err());
+        if !selected {
             continue;
-        }
-        if let Some(max) = config.max_size {
-            if len > max {
-                continue;
-            }
         }
 
-        if let Ok(modified) = metadata.modified() {
-            let modified_secs = modified
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(now);
-            let age = now.saturating_sub(modified_secs);
-            if let Some(min) = config.min_age {
-                if age < min {
-                    continue;
-                }
-            }
-            if let Some(max) = config.max_age {
-                if age > max {
-                    continue;
-                }
-            }
+        // Individual files obey the same rules as files found in directories
+        if !filters.matches(&Candidate {
+            path: &path,
+            root: &path,
+            metadata: &metadata,
+        }) {
+            continue;
         }
 
+        let size = freed(kind, &metadata);
+        let owner = owner_of(&config, &metadata);
         if file_tx
             .send(ScanResult {
                 path,
-                is_dir: false,
-                size: len,
+                kind,
+                size,
+                root,
+                owner,
             })
             .await
             .is_err()
@@ -249,3 +422,167 @@ pub async fn scan_files_direct(
 
     Ok(())
 }
+
+/// Bytes reclaimed by deleting an entry; only regular files count
+fn freed(kind: EntryKind, metadata: &std::fs::Metadata) -> u64 {
+    if kind == EntryKind::File {
+        metadata.len()
+    } else {
+        0
+    }
+}
+
+/// Device id of the filesystem holding an entry
+#[cfg(unix)]
+fn device(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+/// No device ids; the builder rejects --one-file-system off Unix
+#[cfg(not(unix))]
+fn device(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Ownership to report for a selected file, when filtering by it
+fn owner_of(config: &DeleteConfig, metadata: &std::fs::Metadata) -> Option<Ownership> {
+    if config.filters_ownership() {
+        Ownership::of(metadata)
+    } else {
+        None
+    }
+}
+
+/// Applies an [`IgnoreMode`] to paths found under one scan root
+struct IgnoreFilter {
+    mode: IgnoreMode,
+    rules: Option<IgnoreRules>,
+    root: PathBuf,
+    /// Absolute form of `root`, since ignore rules above the root are matched too
+    abs_root: PathBuf,
+}
+
+impl IgnoreFilter {
+    fn new(mode: IgnoreMode, root: &Path) -> Self {
+        let abs_root = match mode {
+            IgnoreMode::Off => PathBuf::new(),
+            _ => std::fs::canonicalize(root)
+                .or_else(|_| std::path::absolute(root))
+                .unwrap_or_else(|_| root.to_path_buf()),
+        };
+        Self {
+            mode,
+            rules: (mode != IgnoreMode::Off).then(IgnoreRules::new),
+            root: root.to_path_buf(),
+            abs_root,
+        }
+    }
+
+    /// Absolute form of a path under the root
+    fn absolute(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => self.abs_root.clone(),
+            Ok(relative) => self.abs_root.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(rules) = &self.rules else {
+            return false;
+        };
+        rules.is_ignored(&self.absolute(path), is_dir)
+    }
+
+    /// Tracked files are never "ignored", whatever the patterns say; directories
+    /// are only removed once empty, so tracked files inside keep them too
+    fn is_ignored_untracked(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(rules) = &self.rules else {
+            return false;
+        };
+        let absolute = self.absolute(path);
+        rules.is_ignored(&absolute, is_dir) && (is_dir || !rules.may_be_tracked(&absolute))
+    }
+
+    /// Whether the walk should visit this entry (prunes ignored subtrees in respect mode)
+    fn enter(&self, path: &Path, is_dir: bool) -> bool {
+        match self.mode {
+            IgnoreMode::Respect if path != self.root => {
+                let git_dir = is_dir && path.file_name().is_some_and(|n| n == ".git");
+                !git_dir && !self.is_ignored(path, is_dir)
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether a visited entry may be selected
+    fn select(&self, path: &Path, is_dir: bool) -> bool {
+        match self.mode {
+            IgnoreMode::Off => true,
+            IgnoreMode::Respect => !self.is_ignored(path, is_dir),
+            IgnoreMode::Only => self.is_ignored_untracked(path, is_dir),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    /// A repository ignoring `*.o` and `build/`, with `tracked.o` force-added
+    fn repo() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("build")).unwrap();
+        fs::write(root.join(".gitignore"), "*.o\nbuild/\n").unwrap();
+        for file in ["main.c", "junk.o", "tracked.o", "build/out.bin"] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        for args in [&["init", "-q"][..], &["add", "-f", "main.c", "tracked.o"]] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        }
+        (dir, root)
+    }
+
+    #[test]
+    fn respect_mode_skips_ignored_entries_and_git_internals() {
+        let (_dir, root) = repo();
+        let ignores = IgnoreFilter::new(IgnoreMode::Respect, &root);
+        assert!(ignores.enter(&root, true));
+        assert!(!ignores.enter(&root.join(".git"), true));
+        assert!(!ignores.enter(&root.join("build"), true));
+        assert!(ignores.select(&root.join("main.c"), false));
+        assert!(!ignores.select(&root.join("junk.o"), false));
+        // Patterns decide in this mode, tracked or not
+        assert!(!ignores.select(&root.join("tracked.o"), false));
+    }
+
+    #[test]
+    fn only_mode_selects_untracked_ignored_entries() {
+        let (_dir, root) = repo();
+        let ignores = IgnoreFilter::new(IgnoreMode::Only, &root);
+        assert!(ignores.enter(&root.join("build"), true));
+        assert!(ignores.select(&root.join("junk.o"), false));
+        assert!(ignores.select(&root.join("build"), true));
+        assert!(ignores.select(&root.join("build/out.bin"), false));
+        assert!(!ignores.select(&root.join("tracked.o"), false));
+        assert!(!ignores.select(&root.join("main.c"), false));
+    }
+
+    #[test]
+    fn off_mode_selects_everything() {
+        let (_dir, root) = repo();
+        let ignores = IgnoreFilter::new(IgnoreMode::Off, &root);
+        assert!(ignores.enter(&root.join(".git"), true));
+        assert!(ignores.select(&root.join("junk.o"), false));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::config::EntryKind;
+use crate::dedupe::KeepRule;
+use crate::error::DeleterError;
+use crate::filter::{RegexTarget, TimeField};
+use crate::namedate::NameDateFallback;
+use crate::space::FreeOrder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Option defaults from the config file.
+///
+/// Every field is optional; flags given on the command line take precedence.
+/// `--yes`, `--delete-root-dir` and `--allow-protected` are deliberately not configurable.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Defaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_list_file: Option<Vec<PathBuf>>,
+    /// Include globs: a single pattern or a list
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub glob: Option<Vec<String>>,
+    /// Exclude globs: a single pattern or a list
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub exclude: Option<Vec<String>>,
+    /// Files with one include glob per line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_from: Option<Vec<PathBuf>>,
+    /// Files with one exclude glob per line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_from: Option<Vec<PathBuf>>,
+    /// Include regexes: a single pattern or a list
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub regex: Option<Vec<String>>,
+    /// Exclude regexes: a single pattern or a list
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub exclude_regex: Option<Vec<String>>,
+    /// Content types: a single pattern or a list, e.g. "image/*"
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub mime: Option<Vec<String>>,
+    /// "name" or "path"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex_target: Option<RegexTarget>,
+    /// Preset names, e.g. ["rust", "node"]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<Vec<String>>,
+    /// Size string, e.g. "10M"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    /// Age string, e.g. "7d"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_age: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<String>,
+    /// Date string, e.g. "2026-01-01" or "2026-01-01T12:00"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<String>,
+    /// "mtime", "atime", "ctime" or "btime"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_field: Option<TimeField>,
+    /// strftime-like format or named-capture regex for dates in file names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_date: Option<String>,
+    /// "skip" or "time-field"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_date_fallback: Option<NameDateFallback>,
+    /// Paths protected along with everything under them, on top of the built-in list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protect: Option<Vec<PathBuf>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Octal mode string, e.g. "-002"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitignore: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_ignored: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirs: Option<bool>,
+    /// Entry kinds, e.g. ["f", "l"]
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub types: Option<Vec<EntryKind>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_symlinks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub one_file_system: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_newest: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_largest: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_daily: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_weekly: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_monthly: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_yearly: Option<usize>,
+    /// Size to keep available, e.g. "50G"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_free_percent: Option<f64>,
+    /// "oldest" or "largest"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_order: Option<FreeOrder>,
+    /// Retention groups: a single glob or a list
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub keep_group: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trash: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallelism: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbose: Option<bool>,
+    /// Dedupe keep rule: "oldest", "newest" or "shortest-path"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<KeepRule>,
+    /// Dedupe: globs for copies to keep in preference, a single pattern or a list
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "one_or_many"
+    )]
+    pub keep_path: Option<Vec<String>>,
+    /// "auto" or a log file path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<u64>,
+    /// Size string, e.g. "10G"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<String>,
+    /// Duration in age units, e.g. "30m" or "1h30m"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<String>,
+}
+
+impl Defaults {
+    /// Fill every unset field from `fallback`
+    pub fn or(self, fallback: Defaults) -> Defaults {
+        Defaults {
+            path_list_file: self.path_list_file.or(fallback.path_list_file),
+            glob: self.glob.or(fallback.glob),
+            exclude: self.exclude.or(fallback.exclude),
+            include_from: self.include_from.or(fallback.include_from),
+            exclude_from: self.exclude_from.or(fallback.exclude_from),
+            regex: self.regex.or(fallback.regex),
+            exclude_regex: self.exclude_regex.or(fallback.exclude_regex),
+            mime: self.mime.or(fallback.mime),
+            regex_target: self.regex_target.or(fallback.regex_target),
+            preset: self.preset.or(fallback.preset),
+            min_size: self.min_size.or(fallback.min_size),
+            max_size: self.max_size.or(fallback.max_size),
+            min_age: self.min_age.or(fallback.min_age),
+            max_age: self.max_age.or(fallback.max_age),
+            older_than: self.older_than.or(fallback.older_than),
+            newer_than: self.newer_than.or(fallback.newer_than),
+            time_field: self.time_field.or(fallback.time_field),
+            name_date: self.name_date.or(fallback.name_date),
+            name_date_fallback: self.name_date_fallback.or(fallback.name_date_fallback),
+            protect: self.protect.or(fallback.protect),
+            user: self.user.or(fallback.user),
+            group: self.group.or(fallback.group),
+            uid: self.uid.or(fallback.uid),
+            gid: self.gid.or(fallback.gid),
+            perm: self.perm.or(fallback.perm),
+            gitignore: self.gitignore.or(fallback.gitignore),
+            only_ignored: self.only_ignored.or(fallback.only_ignored),
+            dirs: self.dirs.or(fallback.dirs),
+            types: self.types.or(fallback.types),
+            broken_symlinks: self.broken_symlinks.or(fallback.broken_symlinks),
+            follow_symlinks: self.follow_symlinks.or(fallback.follow_symlinks),
+            min_depth: self.min_depth.or(fallback.min_depth),
+            max_depth: self.max_depth.or(fallback.max_depth),
+            one_file_system: self.one_file_system.or(fallback.one_file_system),
+            keep_newest: self.keep_newest.or(fallback.keep_newest),
+            keep_largest: self.keep_largest.or(fallback.keep_largest),
+            keep_daily: self.keep_daily.or(fallback.keep_daily),
+            keep_weekly: self.keep_weekly.or(fallback.keep_weekly),
+            keep_monthly: self.keep_monthly.or(fallback.keep_monthly),
+            keep_yearly: self.keep_yearly.or(fallback.keep_yearly),
+            keep_group: self.keep_group.or(fallback.keep_group),
+            free_target: self.free_target.or(fallback.free_target),
+            target_free_percent: self.target_free_percent.or(fallback.target_free_percent),
+            free_order: self.free_order.or(fallback.free_order),
+            trash: self.trash.or(fallback.trash),
+            dry_run: self.dry_run.or(fallback.dry_run),
+            parallelism: self.parallelism.or(fallback.parallelism),
+            verbose: self.verbose.or(fallback.verbose),
+            log: self.log.or(fallback.log),
+            keep: self.keep.or(fallback.keep),
+            keep_path: self.keep_path.or(fallback.keep_path),
+            max_count: self.max_count.or(fallback.max_count),
+            max_bytes: self.max_bytes.or(fallback.max_bytes),
+            time_limit: self.time_limit.or(fallback.time_limit),
+        }
+    }
+}
+
+/// Accept either a single string or a list of strings
+fn one_or_many<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|value| match value {
+            OneOrMany::One(pattern) => vec![pattern],
+            OneOrMany::Many(patterns) => patterns,
+        }),
+    )
+}
+
+/// Contents of `config.toml`: global defaults plus named profiles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub defaults: Defaults,
+    pub profiles: BTreeMap<String, Defaults>,
+    /// File the settings were read from, if any
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Default location: `$XDG_CONFIG_HOME/spacefree/config.toml` or `~/.config/spacefree/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+            .map(|dir| dir.join("spacefree").join("config.toml"))
+    }
+
+    /// Load an explicit config file, or the default one if it exists
+    pub fn load(explicit: Option<&Path>) -> Result<Settings, DeleterError> {
+        let path = match explicit {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Settings::default()),
+            },
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| DeleterError::Config(format!("{}: {}", path.display(), e)))?;
+        let mut settings: Settings = toml::from_str(&content)
+            .map_err(|e| DeleterError::Config(format!("{}: {}", path.display(), e)))?;
+        settings.source = Some(path);
+        Ok(settings)
+    }
+
+    /// Merge the named profile over the global defaults
+    pub fn resolve(&self, profile: Option<&str>) -> Result<Defaults, DeleterError> {
+        let Some(name) = profile else {
+            return Ok(self.defaults.clone());
+        };
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            DeleterError::Config(format!(
+                "unknown profile '{}' (available: {})",
+                name,
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            ))
+        })?;
+        Ok(profile.clone().or(self.defaults.clone()))
+    }
+}
@@ -0,0 +1,377 @@
+use crate::cli::format_size;
+use crate::config::{DeleteConfig, ScanResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// Space reported by the filesystem holding a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsSpace {
+    /// Bytes available to unprivileged users, as `df` reports
+    pub available: u64,
+    pub total: u64,
+}
+
+impl FsSpace {
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> io::Result<Self> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))?;
+        // SAFETY: statvfs is plain data, fully written by a successful call
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: `path` is NUL-terminated and `stat` is a valid, writable statvfs
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // The field types vary by platform (32-bit on some)
+        let block = stat.f_frsize as u64;
+        Ok(Self {
+            available: (stat.f_bavail as u64).saturating_mul(block),
+            total: (stat.f_blocks as u64).saturating_mul(block),
+        })
+    }
+
+    /// No statvfs; the builder rejects free-space targets off Unix
+    #[cfg(not(unix))]
+    pub fn of(_path: &Path) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "free space is only measured on Unix",
+        ))
+    }
+}
+
+/// Free space to reach on the target filesystem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreeGoal {
+    /// At least this many bytes available (`--free-target`)
+    Bytes(u64),
+    /// At least this percentage of the filesystem available (`--target-free-percent`)
+    Percent(f64),
+}
+
+impl FreeGoal {
+    /// The goal in bytes on a filesystem of this size
+    pub fn bytes(&self, space: FsSpace) -> u64 {
+        match *self {
+            FreeGoal::Bytes(bytes) => bytes,
+            FreeGoal::Percent(percent) => (space.total as f64 * percent / 100.0).ceil() as u64,
+        }
+    }
+
+    /// True if a filesystem with this much space available meets the goal
+    pub fn reached(&self, space: FsSpace) -> bool {
+        space.available >= self.bytes(space)
+    }
+}
+
+impl fmt::Display for FreeGoal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreeGoal::Bytes(bytes) => write!(f, "{} free", format_size(*bytes)),
+            FreeGoal::Percent(percent) => write!(f, "{}% free", percent),
+        }
+    }
+}
+
+/// Which matches are deleted first while working toward a free-space goal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FreeOrder {
+    /// Oldest first, by the timestamp the age filters use
+    #[default]
+    Oldest,
+    /// Largest first, so the fewest files go
+    Largest,
+}
+
+impl fmt::Display for FreeOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreeOrder::Oldest => write!(f, "oldest"),
+            FreeOrder::Largest => write!(f, "largest"),
+        }
+    }
+}
+
+/// Delete matches in `order` only until the filesystem holding `path` reaches `goal`
+#[derive(Debug, Clone)]
+pub struct FreeTarget {
+    pub goal: FreeGoal,
+    pub order: FreeOrder,
+    /// Any path on the filesystem to measure (the first root)
+    pub path: PathBuf,
+}
+
+impl FreeTarget {
+    /// Bytes available now and the goal in bytes
+    pub fn status(&self) -> io::Result<(u64, u64)> {
+        let space = FsSpace::of(&self.path)?;
+        Ok((space.available, self.goal.bytes(space)))
+    }
+
+    /// True once the goal is reached; a failed measurement never counts as reached
+    pub fn met(&self) -> bool {
+        FsSpace::of(&self.path).is_ok_and(|space| self.goal.reached(space))
+    }
+}
+
+/// True if every path lives on the same filesystem (always true off Unix)
+pub(crate) fn one_filesystem(paths: &[PathBuf]) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let mut devices = paths
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.dev());
+        let first = devices.next();
+        devices.all(|device| Some(device) == first)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = paths;
+        true
+    }
+}
+
+/// Order matches for a free-space goal and cut the list once their sizes would reach it.
+///
+/// Directories follow the files. Nothing is selected when the filesystem cannot be
+/// measured, since the amount to free is then unknown.
+pub(crate) fn select(
+    results: Vec<ScanResult>,
+    target: &FreeTarget,
+    config: &DeleteConfig,
+) -> Vec<ScanResult> {
+    match FsSpace::of(&target.path) {
+        Ok(space) => select_with(results, target, space, config),
+        Err(e) => {
+            warn!(
+                "Cannot measure free space on {}, selecting nothing: {}",
+                target.path.display(),
+                e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// [`select`] for a filesystem measured at `space`
+fn select_with(
+    results: Vec<ScanResult>,
+    target: &FreeTarget,
+    space: FsSpace,
+    config: &DeleteConfig,
+) -> Vec<ScanResult> {
+    let (available, goal) = (space.available, target.goal.bytes(space));
+    let needed = goal.saturating_sub(available);
+    if needed == 0 {
+        info!(
+            "{} already has {} available, goal {}",
+            target.path.display(),
+            format_size(available),
+            format_size(goal)
+        );
+        return Vec::new();
+    }
+
+    let (dirs, mut entries): (Vec<ScanResult>, Vec<ScanResult>) =
+        results.into_iter().partition(ScanResult::is_dir);
+    match target.order {
+        FreeOrder::Largest => entries.sort_by(|a, b| b.size.cmp(&a.size).then(a.path.cmp(&b.path))),
+        FreeOrder::Oldest => {
+            let source = config.time_source();
+            let mut timed: Vec<(Option<SystemTime>, ScanResult)> = entries
+                .into_iter()
+                .map(|result| {
+                    let time = source.of_path(&result.path, config.follow_symlinks);
+                    (time, result)
+                })
+                .collect();
+            // Entries without a time go last
+            timed.sort_by(|(a, x), (b, y)| {
+                a.is_none()
+                    .cmp(&b.is_none())
+                    .then(a.cmp(b))
+                    .then(x.path.cmp(&y.path))
+            });
+            entries = timed.into_iter().map(|(_, result)| result).collect();
+        }
+    }
+
+    let mut planned = 0u64;
+    let mut selected = Vec::new();
+    for result in entries {
+        if planned >= needed {
+            break;
+        }
+        planned += result.size;
+        selected.push(result);
+    }
+    if planned < needed {
+        warn!(
+            "All matches add up to {}, short of the {} needed",
+            format_size(planned),
+            format_size(needed)
+        );
+    }
+    selected.extend(dirs);
+    selected
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Spacefree;
+    use crate::config::EntryKind;
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    fn space(available: u64, total: u64) -> FsSpace {
+        FsSpace { available, total }
+    }
+
+    #[test]
+    fn byte_goals_ignore_the_filesystem_size() {
+        let goal = FreeGoal::Bytes(100);
+        assert_eq!(goal.bytes(space(0, 50)), 100);
+        assert!(goal.reached(space(100, 50)));
+        assert!(!goal.reached(space(99, 1000)));
+    }
+
+    #[test]
+    fn percent_goals_round_up_against_the_total() {
+        let goal = FreeGoal::Percent(12.5);
+        assert_eq!(goal.bytes(space(0, 1000)), 125);
+        assert_eq!(goal.bytes(space(0, 1001)), 126);
+        assert!(goal.reached(space(125, 1000)));
+        assert!(!goal.reached(space(124, 1000)));
+    }
+
+    /// A match of `size` bytes last modified `age` seconds ago
+    fn file(dir: &Path, name: &str, size: u64, age: u64) -> ScanResult {
+        let path = dir.join(name);
+        fs::write(&path, "").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+        ScanResult {
+            path,
+            kind: EntryKind::File,
+            size,
+            root: 0,
+            owner: None,
+        }
+    }
+
+    fn dir(path: &Path) -> ScanResult {
+        ScanResult {
+            path: path.to_path_buf(),
+            kind: EntryKind::Dir,
+            size: 0,
+            root: 0,
+            owner: None,
+        }
+    }
+
+    /// Names selected toward `goal` on a 1000-byte filesystem with `available` free
+    fn selected(
+        root: &Path,
+        results: Vec<ScanResult>,
+        goal: FreeGoal,
+        order: FreeOrder,
+        available: u64,
+    ) -> Vec<String> {
+        let spacefree = Spacefree::builder()
+            .path(root)
+            .free_target(goal)
+            .free_order(order)
+            .build()
+            .unwrap();
+        let config = spacefree.config();
+        let target = config.free_target.as_ref().unwrap();
+        select_with(results, target, space(available, 1000), config)
+            .iter()
+            .map(|r| {
+                let name = r.path.strip_prefix(root).unwrap().display().to_string();
+                if r.is_dir() {
+                    format!("{}/", name)
+                } else {
+                    name
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn largest_first_until_the_goal_is_planned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let d = tmp.path();
+        fs::create_dir(d.join("sub")).unwrap();
+        let results = vec![
+            dir(&d.join("sub")),
+            file(d, "a", 10, 0),
+            file(d, "b", 50, 0),
+            file(d, "c", 30, 0),
+            file(d, "d", 20, 0),
+        ];
+        // 60 needed: 50 falls short, 50 + 30 reaches it, directories come last
+        assert_eq!(
+            selected(d, results, FreeGoal::Bytes(100), FreeOrder::Largest, 40),
+            ["b", "c", "sub/"]
+        );
+    }
+
+    #[test]
+    fn oldest_first_with_untimed_matches_last() {
+        let tmp = tempfile::tempdir().unwrap();
+        let d = tmp.path();
+        let results = || {
+            let missing = file(d, "gone", 100, 0);
+            fs::remove_file(&missing.path).unwrap();
+            vec![
+                missing,
+                file(d, "new", 10, 60),
+                file(d, "old", 10, 3600),
+                file(d, "older", 10, 7200),
+            ]
+        };
+        // 20 needed (2% of 1000): the two oldest reach it
+        assert_eq!(
+            selected(d, results(), FreeGoal::Percent(2.0), FreeOrder::Oldest, 0),
+            ["older", "old"]
+        );
+        assert_eq!(
+            selected(d, results(), FreeGoal::Bytes(1000), FreeOrder::Oldest, 0),
+            ["older", "old", "new", "gone"]
+        );
+    }
+
+    #[test]
+    fn everything_goes_when_the_goal_is_out_of_reach() {
+        let tmp = tempfile::tempdir().unwrap();
+        let d = tmp.path();
+        let results = vec![dir(d), file(d, "a", 10, 0), file(d, "b", 20, 0)];
+        assert_eq!(
+            selected(d, results, FreeGoal::Bytes(500), FreeOrder::Largest, 0),
+            ["b", "a", "/"]
+        );
+    }
+
+    #[test]
+    fn nothing_goes_once_the_goal_is_met() {
+        let tmp = tempfile::tempdir().unwrap();
+        let d = tmp.path();
+        let results = vec![dir(d), file(d, "a", 10, 0)];
+        assert!(selected(d, results, FreeGoal::Percent(50.0), FreeOrder::Oldest, 500).is_empty());
+    }
+}